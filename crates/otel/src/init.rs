@@ -18,6 +18,7 @@ use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, Registry};
 
 static RESOURCE: OnceLock<Resource> = OnceLock::new();
+static PROVIDERS: OnceLock<(SdkMeterProvider, SdkTracerProvider)> = OnceLock::new();
 
 /// Telemetry initializer.
 pub struct Telemetry {
@@ -91,7 +92,8 @@ impl Telemetry {
         let fmt_layer = tracing_subscriber::fmt::layer();
         let tracer = tracer_provider.tracer(self.app_name);
         let tracing_layer = tracing_opentelemetry::layer().with_tracer(tracer);
-        let metrics_layer = MetricsLayer::new(meter_provider);
+        let metrics_layer = MetricsLayer::new(meter_provider.clone());
+        let _ = PROVIDERS.set((meter_provider, tracer_provider));
 
         // set global default subscriber
         Registry::default()
@@ -105,6 +107,26 @@ impl Telemetry {
     }
 }
 
+/// Flush and shut down the OpenTelemetry providers installed by
+/// [`Telemetry::build`].
+///
+/// Does nothing if telemetry has not been initialized.
+///
+/// # Errors
+///
+/// Returns an error if either provider fails to flush pending telemetry.
+pub fn shutdown() -> Result<()> {
+    let Some((meter_provider, tracer_provider)) = PROVIDERS.get() else {
+        return Ok(());
+    };
+
+    let traces = tracer_provider.shutdown();
+    let metrics = meter_provider.shutdown();
+    traces?;
+    metrics?;
+    Ok(())
+}
+
 fn init_traces(endpoint: Option<&str>) -> Result<SdkTracerProvider> {
     let mut builder = SpanExporter::builder().with_tonic();
     if let Some(endpoint) = endpoint {
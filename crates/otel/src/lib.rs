@@ -8,5 +8,5 @@
 pub mod init;
 pub mod tracing;
 
-pub use init::Telemetry;
+pub use init::{Telemetry, shutdown};
 pub use tracing::*;
@@ -0,0 +1,194 @@
+//! # Admin Server
+//!
+//! A separate HTTP listener exposing liveness, readiness, and runtime
+//! information endpoints for use by orchestrators such as Kubernetes.
+//!
+//! - `/healthz` reports the runtime is alive.
+//! - `/readyz` reports whether every backend is connected and passes its
+//!   health check.
+//! - `/info` reports the runtime name and qwasr version, the linked hosts,
+//!   and the hosted components with their versions.
+
+use std::convert::Infallible;
+use std::env;
+use std::pin::pin;
+use std::time::Duration;
+
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::shutdown::Shutdown;
+use crate::traits::State;
+
+// The maximum time allowed for all backend health checks to complete.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Information about the running runtime, reported by the `/info` endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeInfo {
+    /// The name of the runtime, or of its guest component when it hosts
+    /// only one.
+    pub component: String,
+
+    /// The version of qwasr the runtime was built with.
+    pub runtime_version: String,
+
+    /// The WASI hosts linked to the guest component.
+    pub hosts: Vec<String>,
+
+    /// The guest components hosted by the runtime.
+    pub components: Vec<ComponentInfo>,
+}
+
+/// Information about a guest component, reported by the `/info` endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentInfo {
+    /// The name of the guest component.
+    pub name: String,
+
+    /// The version recorded in the guest component's metadata, if any.
+    pub version: Option<String>,
+
+    /// The number of times the guest component has been reloaded.
+    pub generation: u64,
+}
+
+/// Serve the admin endpoints on `ADMIN_ADDR` until `shutdown` is signalled.
+///
+/// Does nothing when `ADMIN_ADDR` is not set. Readiness is reported as failed
+/// once shutdown has been signalled so no new traffic is routed to the
+/// runtime while in-flight invocations are drained.
+///
+/// # Errors
+///
+/// Will fail if the admin listener cannot be bound.
+pub async fn serve_admin<S: State>(
+    state: &S, hosts: &[&'static str], shutdown: Shutdown,
+) -> Result<()> {
+    let Some(addr) = state.options().admin_addr.clone() else {
+        return Ok(());
+    };
+
+    let listener = TcpListener::bind(&addr).await?;
+    tracing::info!("admin server listening on: {addr}");
+
+    let hosts: Vec<String> = hosts.iter().map(ToString::to_string).collect();
+
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            () = shutdown.signalled() => break,
+        };
+        let state = state.clone();
+        let hosts = hosts.clone();
+        let signal = shutdown.clone();
+
+        tokio::spawn(async move {
+            let connection =
+                http1::Builder::new().serve_connection(
+                    TokioIo::new(stream),
+                    service_fn(move |request| {
+                        let state = state.clone();
+                        let hosts = hosts.clone();
+                        let signal = signal.clone();
+                        async move {
+                            Ok::<_, Infallible>(handle(&state, &hosts, &signal, request).await)
+                        }
+                    }),
+                );
+            if let Err(e) = pin!(connection).await {
+                tracing::debug!("admin connection error: {e:?}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn handle<S: State>(
+    state: &S, hosts: &[String], shutdown: &Shutdown, request: Request<Incoming>,
+) -> Response<Full<Bytes>> {
+    if request.method() != Method::GET {
+        return response(StatusCode::METHOD_NOT_ALLOWED, &json!({"error": "method not allowed"}));
+    }
+
+    match request.uri().path() {
+        "/healthz" => response(StatusCode::OK, &json!({"status": "ok"})),
+        "/readyz" => readiness(state, shutdown).await,
+        "/info" => {
+            let info = RuntimeInfo {
+                component: env::var("COMPONENT").unwrap_or_else(|_| "unknown".into()),
+                runtime_version: env!("CARGO_PKG_VERSION").into(),
+                hosts: hosts.to_vec(),
+                components: state
+                    .guests()
+                    .all()
+                    .iter()
+                    .map(|guest| ComponentInfo {
+                        name: guest.name().to_string(),
+                        version: guest.version(),
+                        generation: guest.generation(),
+                    })
+                    .collect(),
+            };
+            response(StatusCode::OK, &info)
+        }
+        _ => response(StatusCode::NOT_FOUND, &json!({"error": "not found"})),
+    }
+}
+
+// Run backend health checks, reporting the result for each backend.
+async fn readiness<S: State>(state: &S, shutdown: &Shutdown) -> Response<Full<Bytes>> {
+    if shutdown.is_signalled() {
+        return response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &json!({"status": "shutting down", "backends": {}}),
+        );
+    }
+
+    let Ok(checks) = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, state.health()).await else {
+        return response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &json!({"status": "health checks timed out", "backends": {}}),
+        );
+    };
+
+    let mut ready = true;
+    let mut backends = serde_json::Map::new();
+    for (name, result) in checks {
+        let status = match result {
+            Ok(()) => "ok".to_string(),
+            Err(e) => {
+                ready = false;
+                tracing::warn!("backend {name} failed health check: {e}");
+                format!("error: {e}")
+            }
+        };
+        backends.insert(name.to_string(), status.into());
+    }
+
+    let (status, message) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    };
+    response(status, &json!({"status": message, "backends": backends}))
+}
+
+fn response(status: StatusCode, body: &impl Serialize) -> Response<Full<Bytes>> {
+    let body = serde_json::to_vec(body).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .unwrap_or_default()
+}
@@ -0,0 +1,573 @@
+//! # Runtime Builder
+//!
+//! Builds a runtime from hosts and backends registered in code rather than
+//! generated by the `runtime!` macro. Several backends can be registered for
+//! each host, and the hosts enabled, and the backend providing each, are
+//! chosen from the `HOSTS` option when the runtime starts.
+//!
+//! ```rust,ignore
+//! RuntimeBuilder::new()
+//!     .host::<WasiHttp, HttpDefault>("http", "default")
+//!     .host::<WasiKeyValue, KeyValueDefault>("keyvalue", "memory")
+//!     .host::<WasiKeyValue, Redis>("keyvalue", "redis")
+//!     .run_cli()
+//! ```
+//!
+//! `HOSTS` is a comma-separated list of hosts, each optionally naming its
+//! backend as `host=backend`. Hosts without a backend use the first backend
+//! registered for them. When `HOSTS` is unset, every registered host is
+//! enabled with its first backend.
+
+#![allow(missing_docs)]
+// `Backend::connect` and `Server::run` futures are not required to be `Send`
+#![allow(clippy::future_not_send)]
+
+use std::any::Any;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Parser;
+use fromenv::FromEnv;
+use futures::future::{BoxFuture, LocalBoxFuture, join_all, try_join_all};
+use wasmtime::component::Linker;
+use wasmtime_wasi::{ResourceTable, WasiCtx, WasiCtxView, WasiView};
+use wasmtime_wasi_http::p3::{WasiHttpCtx, WasiHttpCtxView, WasiHttpView};
+
+use crate::connect::{BackendOptions, connect_backend};
+use crate::guests::Guests;
+use crate::limits::StoreLimiter;
+use crate::options::RuntimeOptions;
+use crate::policy::Policy;
+use crate::services::{self, Services};
+use crate::shutdown::Shutdown;
+use crate::trace::Trace;
+use crate::traits::{Backend, Host, Server, State};
+use crate::wasi::GuestWasi;
+use crate::{Cli, Command, Compiled, LinkHost, create, inspect};
+
+/// Implemented by hosts that can be added to a runtime with
+/// [`RuntimeBuilder`], for each backend type `B` able to provide them.
+pub trait DynamicHost<B>: Host<RuntimeCtx> + Server<Runtime> + Default + 'static {
+    /// Returns a clone of `backend`, as the context the host's view is
+    /// created from, for a new store.
+    ///
+    /// The host's view of [`RuntimeCtx`] retrieves the context with
+    /// [`RuntimeCtx::context`], using the same type.
+    fn context(backend: &B) -> Box<dyn Any + Send>;
+}
+
+/// Options used to choose hosts and backends.
+///
+/// Options are loaded from environment variables.
+#[derive(Debug, Clone, FromEnv)]
+pub struct BuilderOptions {
+    /// Comma-separated hosts to enable, each in the form `host` or
+    /// `host=backend`. When unset, every registered host is enabled.
+    #[env(from = "HOSTS")]
+    pub hosts: Option<String>,
+}
+
+impl crate::FromEnv for BuilderOptions {
+    fn from_env() -> Result<Self> {
+        Self::from_env().finalize().context("issue loading builder options")
+    }
+}
+
+/// Builds a runtime from registered hosts and backends.
+#[derive(Default)]
+pub struct RuntimeBuilder {
+    registrations: Vec<Registration>,
+}
+
+// A backend registered for a host.
+#[derive(Debug, Clone)]
+struct Registration {
+    host: &'static str,
+    backend: &'static str,
+    add_to_linker: fn(&mut Linker<RuntimeCtx>) -> Result<()>,
+    options: fn() -> Vec<String>,
+    connect: for<'a> fn(
+        &'static str,
+        &'a BackendOptions,
+        &'a Shutdown,
+    ) -> LocalBoxFuture<'a, Result<Connected>>,
+    serve: for<'a> fn(&'a Runtime, Shutdown) -> LocalBoxFuture<'a, Result<()>>,
+    replay: for<'a> fn(&'a Runtime, &'a Trace, Shutdown) -> Replayed<'a>,
+}
+
+// The result of replaying a trace, when the host replays it.
+type Replayed<'a> = LocalBoxFuture<'a, Option<Result<()>>>;
+
+// A connected backend.
+struct Connected {
+    host: &'static str,
+    context: Box<dyn Fn() -> Box<dyn Any + Send> + Send + Sync>,
+    health: Box<dyn Fn() -> BoxFuture<'static, Result<()>> + Send + Sync>,
+}
+
+impl RuntimeBuilder {
+    /// Create a builder with no hosts registered.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the backend `B`, named `backend`, as a provider of the host
+    /// `H`, named `host`.
+    #[must_use]
+    pub fn host<H, B>(mut self, host: &'static str, backend: &'static str) -> Self
+    where
+        H: DynamicHost<B>,
+        B: Backend + Clone + 'static,
+    {
+        self.registrations.push(Registration {
+            host,
+            backend,
+            add_to_linker: <H as Host<RuntimeCtx>>::add_to_linker,
+            options: options::<H, B>,
+            connect: connect::<H, B>,
+            serve: serve::<H, B>,
+            replay: replay::<H, B>,
+        });
+        self
+    }
+
+    /// Run the command given on the command line.
+    ///
+    /// The configuration file given to the command is loaded before the
+    /// async runtime is built, so this must be called from a synchronous
+    /// `main` rather than within an async runtime.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the configuration cannot be loaded, the async runtime
+    /// cannot be built, or the command fails.
+    pub fn run_cli(&self) -> Result<ExitCode> {
+        let command = Cli::parse().command;
+        command.configure(&self.sections(), &self.options())?;
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("building async runtime")?
+            .block_on(self.run_command(command))
+    }
+
+    // Run `command`, once its configuration has been loaded.
+    async fn run_command(&self, command: Command) -> Result<ExitCode> {
+        match command {
+            Command::Run { wasm, .. } => self.run(&wasm).await.map(|()| ExitCode::SUCCESS),
+            Command::Exec { wasm, args, .. } => self.exec(&wasm, &args).await,
+            Command::Replay { wasm, trace, .. } => {
+                self.replay(&wasm, &trace).await.map(|()| ExitCode::SUCCESS)
+            }
+            Command::Inspect { wasm, json } => {
+                self.inspect(&wasm, json).map(|()| ExitCode::SUCCESS)
+            }
+            #[cfg(feature = "jit")]
+            Command::Compile { wasm, output, sign } => {
+                crate::compile(&wasm, output, sign).map(|()| ExitCode::SUCCESS)
+            }
+        }
+    }
+
+    /// Run the `wasm` guests with the hosts chosen by `HOSTS`.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the hosts cannot be chosen, the guests cannot be
+    /// compiled or linked, a backend cannot be connected, or a server fails.
+    pub async fn run(&self, wasm: &[PathBuf]) -> Result<()> {
+        let selected = self.select()?;
+        let (compiled, runtime) = Runtime::new(wasm, &selected)?;
+
+        // reload guests on SIGHUP or when their wasm file changes
+        let reload_watch = compiled.options().reload_watch();
+        for reloader in compiled.reloaders(&runtime.guests) {
+            reloader.start(reload_watch);
+        }
+
+        services::start(&runtime).await.context("starting runtime services")
+    }
+
+    /// Run the `wasm` command guest once with the hosts chosen by `HOSTS`,
+    /// passing it `args`, and return its exit code.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the hosts cannot be chosen, the guest cannot be compiled
+    /// or linked, a backend cannot be connected, or the guest traps.
+    pub async fn exec(&self, wasm: &Path, args: &[String]) -> Result<ExitCode> {
+        let selected = self.select()?;
+        let (_, runtime) = Runtime::new(&[wasm.to_path_buf()], &selected)?;
+        services::run_command(&runtime, args).await
+    }
+
+    /// Replay the invocation of the `wasm` guest recorded in `trace`, with the
+    /// hosts chosen by `HOSTS`.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the trace cannot be loaded, the hosts cannot be chosen,
+    /// the guest cannot be compiled or linked, a backend cannot be connected,
+    /// or no enabled host replays the trace.
+    pub async fn replay(&self, wasm: &Path, trace: &Path) -> Result<()> {
+        let trace = Trace::load(trace)?;
+        let selected = self.select()?;
+        let (_, runtime) = Runtime::new(&[wasm.to_path_buf()], &selected)?;
+        services::replay(&runtime, &trace).await
+    }
+
+    /// Inspect the `wasm` guest against the hosts chosen by `HOSTS`.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the hosts cannot be chosen, the guest cannot be
+    /// inspected, or any of its imports is not provided.
+    pub fn inspect(&self, wasm: &Path, json: bool) -> Result<()> {
+        let hosts: Vec<LinkHost<RuntimeCtx>> = self
+            .select()?
+            .iter()
+            .map(|registration| (registration.host, registration.add_to_linker))
+            .collect();
+        inspect(wasm, &hosts)?.print(json)
+    }
+
+    // The environment variables read by the registered hosts and backends.
+    fn options(&self) -> Vec<String> {
+        self.registrations.iter().flat_map(|registration| (registration.options)()).collect()
+    }
+
+    // Choose the registered hosts and backends enabled by `HOSTS`.
+    fn select(&self) -> Result<Vec<&Registration>> {
+        let options = <BuilderOptions as crate::FromEnv>::from_env()?;
+        self.choose(options.hosts.as_deref())
+    }
+
+    // The configuration file sections of the registered hosts and backends.
+    fn sections(&self) -> Vec<&'static str> {
+        let mut sections = Vec::new();
+        for registration in &self.registrations {
+            for section in [registration.host, registration.backend] {
+                if !sections.contains(&section) {
+                    sections.push(section);
+                }
+            }
+        }
+        sections
+    }
+
+    // Choose the registered hosts and backends enabled by `hosts`.
+    fn choose(&self, hosts: Option<&str>) -> Result<Vec<&Registration>> {
+        let Some(hosts) = hosts else {
+            let mut selected: Vec<&Registration> = Vec::new();
+            for registration in &self.registrations {
+                if !selected.iter().any(|s| s.host == registration.host) {
+                    selected.push(registration);
+                }
+            }
+            return Ok(selected);
+        };
+
+        let mut selected: Vec<&Registration> = Vec::new();
+        for entry in hosts.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (host, backend) = match entry.split_once('=') {
+                Some((host, backend)) => (host, Some(backend)),
+                None => (entry, None),
+            };
+            let mut candidates =
+                self.registrations.iter().filter(|registration| registration.host == host);
+            let registration = match backend {
+                Some(backend) => candidates.find(|registration| registration.backend == backend),
+                None => candidates.next(),
+            };
+
+            let Some(registration) = registration else {
+                let available = self
+                    .registrations
+                    .iter()
+                    .map(|r| format!("{}={}", r.host, r.backend))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                bail!("no host registered for `{entry}` in `HOSTS`: available are {available}");
+            };
+            if selected.iter().any(|s| s.host == host) {
+                bail!("host `{host}` is enabled more than once in `HOSTS`");
+            }
+            selected.push(registration);
+        }
+
+        Ok(selected)
+    }
+}
+
+// The environment variables read by the host `H` and the backend `B`.
+fn options<H, B>() -> Vec<String>
+where
+    H: DynamicHost<B>,
+    B: Backend,
+{
+    let mut options = <H as Host<RuntimeCtx>>::env_vars();
+    options.extend(<B::ConnectOptions as crate::FromEnv>::env_vars());
+    options
+}
+
+// Connect to the backend `B` providing the host `H`, named `host`.
+fn connect<'a, H, B>(
+    host: &'static str, options: &'a BackendOptions, shutdown: &'a Shutdown,
+) -> LocalBoxFuture<'a, Result<Connected>>
+where
+    H: DynamicHost<B>,
+    B: Backend + Clone + 'static,
+{
+    Box::pin(async move {
+        let backend = Arc::new(connect_backend::<B>(host, options, shutdown).await?);
+        let health = Arc::clone(&backend);
+
+        Ok(Connected {
+            host,
+            context: Box::new(move || H::context(&backend)),
+            health: Box::new(move || {
+                let backend = Arc::clone(&health);
+                Box::pin(async move { backend.health_check().await })
+            }),
+        })
+    })
+}
+
+// Run the server of the host `H`.
+fn serve<H, B>(runtime: &Runtime, shutdown: Shutdown) -> LocalBoxFuture<'_, Result<()>>
+where
+    H: DynamicHost<B>,
+{
+    Box::pin(async move { H::default().run(runtime, shutdown).await })
+}
+
+// Replay `trace` with the server of the host `H`.
+fn replay<'a, H, B>(runtime: &'a Runtime, trace: &'a Trace, shutdown: Shutdown) -> Replayed<'a>
+where
+    H: DynamicHost<B>,
+{
+    Box::pin(async move { H::default().replay(runtime, trace, shutdown).await })
+}
+
+/// The state of a runtime built by [`RuntimeBuilder`], holding the
+/// pre-instantiated guests and connected backends.
+#[derive(Clone)]
+pub struct Runtime {
+    guests: Guests<RuntimeCtx>,
+    options: RuntimeOptions,
+    policy: Arc<Policy>,
+    wasi: Arc<GuestWasi>,
+    hosts: Arc<[&'static str]>,
+    selected: Arc<[Registration]>,
+    backends: Arc<OnceLock<Vec<Connected>>>,
+}
+
+impl Runtime {
+    // Compile the `wasm` guests and link the selected hosts.
+    fn new(wasm: &[PathBuf], selected: &[&Registration]) -> Result<(Compiled<RuntimeCtx>, Self)> {
+        let mut compiled = create::<RuntimeCtx>(wasm).context("creating runtime")?;
+        for registration in selected {
+            compiled.link_with(registration.add_to_linker)?;
+        }
+
+        let runtime = Self {
+            guests: compiled.pre_instantiate()?,
+            options: compiled.options().clone(),
+            policy: compiled.policy(),
+            wasi: compiled.wasi(),
+            hosts: selected.iter().map(|registration| registration.host).collect(),
+            selected: selected.iter().map(|registration| (*registration).clone()).collect(),
+            backends: Arc::new(OnceLock::new()),
+        };
+        Ok((compiled, runtime))
+    }
+
+    // The selected registration of the host named `host`.
+    fn registration(&self, host: &str) -> Option<&Registration> {
+        self.selected.iter().find(|registration| registration.host == host)
+    }
+}
+
+impl Services for Runtime {
+    fn hosts(&self) -> &[&'static str] {
+        &self.hosts
+    }
+
+    async fn connect(&self, shutdown: &Shutdown) -> Result<()> {
+        let options = &self.options.backends;
+        let backends = try_join_all(self.selected.iter().map(|registration| async {
+            (registration.connect)(registration.host, options, shutdown).await.with_context(|| {
+                format!("connecting {} backend `{}`", registration.host, registration.backend)
+            })
+        }))
+        .await?;
+        let _ = self.backends.set(backends);
+        Ok(())
+    }
+
+    fn serve(&self, host: &'static str, shutdown: Shutdown) -> LocalBoxFuture<'_, Result<()>> {
+        let Some(registration) = self.registration(host) else {
+            return Box::pin(async move { Err(anyhow!("host `{host}` is not enabled")) });
+        };
+        (registration.serve)(self, shutdown)
+    }
+
+    fn replay<'a>(
+        &'a self, host: &'static str, trace: &'a Trace, shutdown: Shutdown,
+    ) -> Replayed<'a> {
+        let Some(registration) = self.registration(host) else {
+            return Box::pin(async { None });
+        };
+        (registration.replay)(self, trace, shutdown)
+    }
+}
+
+impl State for Runtime {
+    type StoreCtx = RuntimeCtx;
+
+    fn store(&self) -> Self::StoreCtx {
+        let contexts = self
+            .backends
+            .get()
+            .expect("backends should be connected before guests are invoked")
+            .iter()
+            .map(|backend| (backend.context)())
+            .collect();
+        RuntimeCtx::new(&self.options, Arc::clone(&self.policy), &self.wasi, contexts)
+    }
+
+    fn guests(&self) -> &Guests<Self::StoreCtx> {
+        &self.guests
+    }
+
+    fn options(&self) -> &RuntimeOptions {
+        &self.options
+    }
+
+    fn wasi(&self) -> &GuestWasi {
+        &self.wasi
+    }
+
+    fn limiter(ctx: &mut Self::StoreCtx) -> &mut StoreLimiter {
+        &mut ctx.limits
+    }
+
+    async fn health(&self) -> Vec<(&'static str, Result<()>)> {
+        let Some(backends) = self.backends.get() else {
+            return self.hosts.iter().map(|host| (*host, Err(anyhow!("not connected")))).collect();
+        };
+        join_all(backends.iter().map(|backend| async { (backend.host, (backend.health)().await) }))
+            .await
+    }
+}
+
+/// Per-guest instance data for a runtime built by [`RuntimeBuilder`].
+///
+/// Host contexts are held by type, and retrieved by each host's view with
+/// [`RuntimeCtx::context`].
+pub struct RuntimeCtx {
+    pub table: ResourceTable,
+    pub wasi: WasiCtx,
+    pub limits: StoreLimiter,
+    pub policy: Arc<Policy>,
+    contexts: Vec<Box<dyn Any + Send>>,
+}
+
+impl RuntimeCtx {
+    /// Create the store context for a guest invocation, holding the host
+    /// `contexts` returned by [`DynamicHost::context`].
+    #[must_use]
+    pub fn new(
+        options: &RuntimeOptions, policy: Arc<Policy>, wasi: &GuestWasi,
+        contexts: Vec<Box<dyn Any + Send>>,
+    ) -> Self {
+        Self {
+            table: ResourceTable::new(),
+            wasi: wasi.wasi_ctx(),
+            limits: StoreLimiter::new(options.limits.clone()),
+            policy,
+            contexts,
+        }
+    }
+
+    /// Returns the host context of type `C`, together with the resource
+    /// table and capability policy, or `None` when no enabled host provides a
+    /// context of type `C`.
+    ///
+    /// Host functions are only linked for enabled hosts, so a host's view
+    /// only finds no context when its view and [`DynamicHost::context`] use
+    /// different types.
+    pub fn context<C: 'static>(&mut self) -> Option<(&mut C, &mut ResourceTable, &Policy)> {
+        let context = self.contexts.iter_mut().find_map(|context| context.downcast_mut::<C>())?;
+        Some((context, &mut self.table, &self.policy))
+    }
+}
+
+impl WasiView for RuntimeCtx {
+    fn ctx(&mut self) -> WasiCtxView<'_> {
+        WasiCtxView {
+            ctx: &mut self.wasi,
+            table: &mut self.table,
+        }
+    }
+}
+
+// `WasiHttpView` is defined by wasmtime, so can only be implemented here.
+impl WasiHttpView for RuntimeCtx {
+    fn http(&mut self) -> WasiHttpCtxView<'_> {
+        let (ctx, table, _) =
+            self.context::<Box<dyn WasiHttpCtx>>().expect("HTTP host context should be registered");
+        WasiHttpCtxView {
+            ctx: ctx.as_mut(),
+            table,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> RuntimeBuilder {
+        let registration = |host, backend| Registration {
+            host,
+            backend,
+            add_to_linker: |_| Ok(()),
+            options: Vec::new,
+            connect: |_, _, _| Box::pin(async { bail!("not connected") }),
+            serve: |_, _| Box::pin(async { Ok(()) }),
+            replay: |_, _, _| Box::pin(async { None }),
+        };
+        RuntimeBuilder {
+            registrations: vec![
+                registration("http", "default"),
+                registration("keyvalue", "memory"),
+                registration("keyvalue", "redis"),
+            ],
+        }
+    }
+
+    #[test]
+    fn choose() {
+        let builder = builder();
+        let chosen = |hosts| {
+            builder
+                .choose(hosts)
+                .expect("should choose")
+                .iter()
+                .map(|r| format!("{}={}", r.host, r.backend))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(chosen(None), vec!["http=default", "keyvalue=memory"]);
+        assert_eq!(chosen(Some("keyvalue")), vec!["keyvalue=memory"]);
+        assert_eq!(chosen(Some("http, keyvalue=redis")), vec!["http=default", "keyvalue=redis"]);
+
+        builder.choose(Some("sql")).expect_err("should reject unknown host");
+        builder.choose(Some("keyvalue=nats")).expect_err("should reject unknown backend");
+        builder.choose(Some("keyvalue,keyvalue=redis")).expect_err("should reject duplicates");
+    }
+}
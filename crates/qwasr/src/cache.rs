@@ -0,0 +1,275 @@
+//! # Precompile Cache
+//!
+//! Pre-compiled components are stored with a header identifying the wasm they
+//! were compiled from and the engine they were compiled for. The header is
+//! checked before the component is deserialized, so stale or foreign
+//! artifacts are rejected with a precise error. The header does not replace
+//! wasmtime's own compatibility check, made when the component is
+//! deserialized, which rejects artifacts from another wasmtime version or
+//! with incompatible compiler settings.
+//!
+//! When a cache directory is configured, wasm components are compiled once
+//! and stored in the directory, keyed by the hash of the wasm and of the
+//! engine configuration.
+
+use std::env;
+#[cfg(feature = "jit")]
+use std::fs;
+#[cfg(feature = "jit")]
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+use wasmtime::component::Component;
+use wasmtime::{Engine, Precompiled};
+
+use crate::options::RuntimeOptions;
+
+// Identifies an artifact written by `serialize`.
+const MAGIC: &[u8; 8] = b"QWASRBIN";
+
+// Incremented whenever the header layout changes.
+const FORMAT: u8 = 1;
+
+// The qwasr version is recorded in the header, so artifacts are recompiled
+// when qwasr is upgraded. It does not identify the wasmtime version: wasmtime
+// checks that itself when the artifact is deserialized.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// magic (8) | format (1) | version length (1) | version (22) | engine (32) |
+// wasm (32). The header is a multiple of 16 bytes so the compiled component
+// following it stays aligned.
+const VERSION_LEN: usize = 22;
+const HEADER_LEN: usize = 96;
+
+/// Serialize `component`, compiled from `wasm` with `options`, with a header
+/// identifying the wasm and the engine it was compiled for.
+///
+/// # Errors
+///
+/// Returns an error if the component cannot be serialized, or the qwasr
+/// version does not fit in the header.
+#[cfg(feature = "jit")]
+pub fn serialize(options: &RuntimeOptions, wasm: &[u8], component: &Component) -> Result<Vec<u8>> {
+    if VERSION.len() > VERSION_LEN {
+        bail!("qwasr version {VERSION} is longer than the {VERSION_LEN} bytes recorded");
+    }
+    let serialized = component.serialize()?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + serialized.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(FORMAT);
+    bytes.push(u8::try_from(VERSION.len())?);
+    bytes.extend_from_slice(VERSION.as_bytes());
+    bytes.resize(MAGIC.len() + 2 + VERSION_LEN, 0);
+    bytes.extend_from_slice(&engine_hash(options));
+    bytes.extend_from_slice(&Sha256::digest(wasm));
+    bytes.extend_from_slice(&serialized);
+    Ok(bytes)
+}
+
+/// Load a component from `bytes`, holding either an artifact produced by
+/// [`serialize`] or a wasm component, for `engine`, created with `options`.
+///
+/// Wasm components are compiled, or loaded from the compile cache directory
+/// when an artifact for the same wasm and engine configuration is found
+/// there.
+pub fn load(engine: &Engine, options: &RuntimeOptions, bytes: &[u8]) -> Result<Component> {
+    if bytes.starts_with(MAGIC) {
+        return deserialize(engine, options, bytes, None);
+    }
+    if Engine::detect_precompiled(bytes).is_some() {
+        bail!(
+            "pre-compiled component was not produced by `qwasr compile`: recompile it from the \
+             original wasm"
+        );
+    }
+    compile(engine, options, bytes)
+}
+
+#[cfg(feature = "jit")]
+fn compile(engine: &Engine, options: &RuntimeOptions, wasm: &[u8]) -> Result<Component> {
+    let wasm_hash: [u8; 32] = Sha256::digest(wasm).into();
+    let entry = options.compile_cache_dir.as_ref().map(|dir| {
+        let engine_hash = hex::encode(&engine_hash(options)[..8]);
+        dir.join(format!("{}-{engine_hash}.bin", hex::encode(wasm_hash)))
+    });
+
+    if let Some(entry) = &entry
+        && entry.exists()
+    {
+        let cached = fs::read(entry).map_err(Into::into);
+        match cached.and_then(|cached| deserialize(engine, options, &cached, Some(&wasm_hash))) {
+            Ok(component) => {
+                tracing::debug!(entry = %entry.display(), "component loaded from compile cache");
+                return Ok(component);
+            }
+            Err(e) => {
+                tracing::warn!(entry = %entry.display(), "discarding compile cache entry: {e:#}");
+            }
+        }
+    }
+
+    let component = Component::new(engine, wasm)?;
+
+    // failing to cache the component does not prevent it being run
+    if let Some(entry) = entry
+        && let Err(e) = store(options, wasm, &component, &entry)
+    {
+        tracing::warn!(entry = %entry.display(), "issue writing compile cache entry: {e:#}");
+    }
+
+    Ok(component)
+}
+
+#[cfg(not(feature = "jit"))]
+fn compile(_: &Engine, _: &RuntimeOptions, _: &[u8]) -> Result<Component> {
+    bail!("component is not pre-compiled: enable the `jit` feature to load wasm32 files")
+}
+
+// Write the compiled component to the cache. The entry is written to a
+// temporary file and renamed so concurrent runtimes never read a partial
+// entry.
+#[cfg(feature = "jit")]
+fn store(options: &RuntimeOptions, wasm: &[u8], component: &Component, entry: &Path) -> Result<()> {
+    if let Some(dir) = entry.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = entry.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temp, serialize(options, wasm, component)?)?;
+    fs::rename(&temp, entry)?;
+    Ok(())
+}
+
+// Check the header and deserialize the component following it. When
+// `wasm_hash` is set, the artifact must have been compiled from that wasm.
+fn deserialize(
+    engine: &Engine, options: &RuntimeOptions, bytes: &[u8], wasm_hash: Option<&[u8; 32]>,
+) -> Result<Component> {
+    let Some((header, serialized)) = bytes.split_at_checked(HEADER_LEN) else {
+        bail!("pre-compiled component is truncated");
+    };
+    let (magic, rest) = header.split_at(MAGIC.len());
+    let (format, rest) = (rest[0], &rest[1..]);
+    let (version_len, rest) = (usize::from(rest[0]), &rest[1..]);
+    let (version, rest) = rest.split_at(VERSION_LEN);
+    let (engine_bytes, wasm_bytes) = rest.split_at(32);
+
+    if magic != MAGIC {
+        bail!("pre-compiled component was not produced by `qwasr compile`");
+    }
+    if format != FORMAT {
+        bail!("pre-compiled component has header format {format}, expected {FORMAT}: recompile it");
+    }
+    let version = version.get(..version_len).and_then(|v| str::from_utf8(v).ok());
+    if version != Some(VERSION) {
+        bail!(
+            "component was pre-compiled by qwasr {}, but this is qwasr {VERSION}: recompile it",
+            version.unwrap_or("(unknown)")
+        );
+    }
+    if engine_bytes != engine_hash(options) {
+        bail!(
+            "component was pre-compiled for a different engine configuration or target: \
+             recompile it with the runtime's current options"
+        );
+    }
+    if let Some(wasm_hash) = wasm_hash
+        && wasm_bytes != wasm_hash
+    {
+        bail!("pre-compiled component was compiled from different wasm");
+    }
+    if Engine::detect_precompiled(serialized) != Some(Precompiled::Component) {
+        bail!("pre-compiled artifact is not a component");
+    }
+
+    // SAFETY: The header shows the artifact was produced by `serialize`, so
+    // is a component compiled by wasmtime rather than arbitrary bytes.
+    // Wasmtime checks the artifact was compiled by the same wasmtime version
+    // with compatible settings before loading it.
+    unsafe { Component::deserialize(engine, serialized) }
+        .context("issue deserializing pre-compiled component")
+}
+
+// A digest of the target and the runtime options that affect compiled code.
+// Artifacts are only loaded by a runtime with the same digest. Unlike
+// wasmtime's compatibility hash, it does not need a compiler, so is checked
+// whether or not the `jit` feature is enabled.
+fn engine_hash(options: &RuntimeOptions) -> [u8; 32] {
+    let settings = format!(
+        "{}-{} fuel={} pooling={} memory_bytes={:?} table_elements={:?}",
+        env::consts::ARCH,
+        env::consts::OS,
+        options.guest_fuel.is_some(),
+        options.pooling.enabled,
+        options.limits.memory_bytes,
+        options.limits.table_elements,
+    );
+    Sha256::digest(settings).into()
+}
+
+#[cfg(all(test, feature = "jit"))]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::engine::config;
+    use crate::traits::FromEnv;
+
+    // the smallest valid component
+    const EMPTY: &[u8] = b"\0asm\x0d\0\x01\0";
+
+    fn engine(fuel: bool, cache: Option<PathBuf>) -> (Engine, RuntimeOptions) {
+        let mut options = <RuntimeOptions as FromEnv>::from_env().expect("should load options");
+        options.guest_fuel = fuel.then_some(1_000_000);
+        options.compile_cache_dir = cache;
+        let engine = Engine::new(&config(&options)).expect("should create engine");
+        (engine, options)
+    }
+
+    #[test]
+    fn compatibility() {
+        let (engine, options) = engine(false, None);
+        let component = Component::new(&engine, EMPTY).expect("should compile");
+        let bytes = serialize(&options, EMPTY, &component).expect("should serialize");
+
+        load(&engine, &options, &bytes).expect("should load");
+
+        let (fuel_engine, fuel_options) = self::engine(true, None);
+        let Err(e) = load(&fuel_engine, &fuel_options, &bytes) else {
+            panic!("should reject incompatible engine");
+        };
+        assert!(e.to_string().contains("different engine configuration"));
+
+        let mut stale = bytes;
+        stale[10..14].copy_from_slice(b"0.0.");
+        let Err(e) = load(&engine, &options, &stale) else {
+            panic!("should reject other versions");
+        };
+        assert!(e.to_string().contains("recompile it"));
+
+        let foreign = component.serialize().expect("should serialize");
+        let Err(e) = load(&engine, &options, &foreign) else {
+            panic!("should reject foreign artifacts");
+        };
+        assert!(e.to_string().contains("not produced by `qwasr compile`"));
+    }
+
+    #[test]
+    fn cache() {
+        let dir = env::temp_dir().join(format!("qwasr-cache-{}", std::process::id()));
+        let (engine, options) = engine(false, Some(dir.clone()));
+
+        load(&engine, &options, EMPTY).expect("should compile");
+        let entries: Vec<_> = fs::read_dir(&dir).expect("should read").collect();
+        assert_eq!(entries.len(), 1);
+
+        // corrupt entries are replaced
+        let entry = entries[0].as_ref().expect("should be entry").path();
+        fs::write(&entry, b"corrupt").expect("should write");
+        load(&engine, &options, EMPTY).expect("should recompile");
+        assert!(fs::read(&entry).expect("should read").starts_with(MAGIC));
+
+        fs::remove_dir_all(&dir).expect("should remove");
+    }
+}
@@ -2,45 +2,73 @@
 
 use std::fs::{self, File};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Result, anyhow};
+use wasmtime::Engine;
 use wasmtime::component::Component;
-use wasmtime::{Config, Engine};
+
+use crate::cache::serialize;
+use crate::engine::config;
+use crate::options::RuntimeOptions;
+use crate::signature;
+use crate::traits::FromEnv;
 
 /// Compile `wasm32-wasip2` component.
 ///
+/// The compiled component can only be run by the same version of qwasr, with
+/// the same runtime options affecting compilation (for example,
+/// `GUEST_FUEL` and `POOLING_ALLOCATOR`), on the same target.
+///
 /// For example, to compile the `http` component, run:
 ///
 /// ```bash
 /// cargo build --package otel --target wasm32-wasip2 --release
 /// ```
 ///
+/// The compiled component is written to `output`, or to stdout when
+/// `output` is not set.
+///
+/// When trusted keys are configured, the wasm component's signature is
+/// verified before it is compiled. When `sign` is set, the compiled component
+/// is signed with the ed25519 private key in that PEM file and the signature
+/// written alongside it, with `.sig` appended to its file name. Signed
+/// components are written next to the wasm component when `output` is not
+/// set, for example to `guest.bin` and `guest.bin.sig`.
+///
 /// # Errors
 ///
 /// Returns an error if the wasm component cannot be loaded from the specified
-/// path, cannot be compiled, or cannot be serialized to the specified output
-/// directory.
-pub fn compile(wasm: &PathBuf, output: Option<PathBuf>) -> Result<()> {
+/// path, fails signature verification, cannot be compiled, or cannot be
+/// serialized to the specified output directory.
+pub fn compile(wasm: &PathBuf, output: Option<PathBuf>, sign: Option<PathBuf>) -> Result<()> {
     let Some(file_name) = wasm.file_name() else {
         return Err(anyhow!("invalid file name"));
     };
+    let bin_name = Path::new(file_name).with_extension("bin");
+
+    // the signature is written next to the compiled component, so a signed
+    // component cannot be written to stdout
+    let output = match output {
+        None if sign.is_some() => Some(wasm.with_file_name(&bin_name)),
+        output => output,
+    };
 
     // compile component
-    let mut config = Config::new();
-    config.async_support(true);
-    config.wasm_component_model_async(true);
-    let engine = Engine::new(&config)?;
-    let component = Component::from_file(&engine, wasm)?;
-    let serialized = component.serialize()?;
+    let options = <RuntimeOptions as FromEnv>::from_env()?;
+    let engine = Engine::new(&config(&options))?;
+    let bytes = fs::read(wasm)?;
+    if let Some(trusted) = &options.trusted_keys {
+        signature::verify(wasm, &bytes, trusted)?;
+    }
+    let component = Component::new(&engine, &bytes)?;
+    let serialized = serialize(&options, &bytes, &component)?;
 
     // output to file or stdout
     if let Some(mut out_path) = output {
         // output to file
         if out_path.is_dir() {
-            let file_name = file_name.to_string_lossy().to_string();
-            let file_name = file_name.replace(".wasm", ".bin");
-            out_path.push(file_name);
+            out_path.push(&bin_name);
         }
 
         // create output directory if it doesn't exist
@@ -51,6 +79,13 @@ pub fn compile(wasm: &PathBuf, output: Option<PathBuf>) -> Result<()> {
         }
 
         File::create(&out_path)?.write_all(&serialized)?;
+
+        // signatures over the wasm do not cover the compiled code, so the
+        // compiled component is signed afresh
+        if let Some(key) = sign {
+            let signature = signature::sign(&serialized, &key)?;
+            fs::write(signature::signature_path(&out_path), format!("{signature}\n"))?;
+        }
     } else {
         // output to stdout
         let mut stdout = io::stdout().lock();
@@ -60,4 +95,27 @@ pub fn compile(wasm: &PathBuf, output: Option<PathBuf>) -> Result<()> {
     Ok(())
 }
 
-//
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn sign_next_to_wasm() {
+        let dir = env::temp_dir().join(format!("qwasr-compile-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("should create dir");
+        let wasm = dir.join("guest.wasm");
+        fs::write(&wasm, b"\0asm\x0d\0\x01\0").expect("should write");
+
+        let (key, trusted) = signature::test_key(&dir);
+
+        compile(&wasm, None, Some(key)).expect("should compile");
+
+        let bin = dir.join("guest.bin");
+        let bytes = fs::read(&bin).expect("should write compiled component");
+        signature::verify(&bin, &bytes, &trusted).expect("should verify");
+
+        fs::remove_dir_all(&dir).expect("should remove");
+    }
+}
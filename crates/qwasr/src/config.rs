@@ -0,0 +1,299 @@
+//! # Configuration File
+//!
+//! Loads runtime, host, and backend configuration from a TOML file.
+//!
+//! Each section of the file configures a single host or backend, and each key
+//! corresponds to the environment variable named `<SECTION>_<KEY>`. Keys in
+//! the `[runtime]` section correspond to the environment variable named
+//! `<KEY>`. For example:
+//!
+//! ```toml
+//! [runtime]
+//! guest_timeout_ms = 5000   # GUEST_TIMEOUT_MS
+//!
+//! [http]
+//! addr = "0.0.0.0:8080"     # HTTP_ADDR
+//!
+//! [sql]
+//! database = "app.db"       # SQL_DATABASE
+//! ```
+//!
+//! Values are loaded into the process environment so they are read by each
+//! backend's `ConnectOptions`. Environment variables that are already set
+//! override values in the file.
+//!
+//! Keys in the `[runtime]` section must name a runtime option, and every other
+//! section must be named after one of the runtime's hosts or backends, with
+//! keys setting a variable read by one of them. The file is loaded before the
+//! async runtime is built, since the environment can only be modified safely
+//! while the process has a single thread.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::{env, fs};
+
+use anyhow::{Context, Result, anyhow, bail};
+use toml::{Table, Value};
+
+use crate::{
+    BuilderOptions, Command, RuntimeOptions, enable_profiling, enable_recording, requirement_vars,
+};
+
+// The section whose keys are not prefixed with the section name.
+const RUNTIME_SECTION: &str = "runtime";
+
+// The environment variables read by the runtime, which keys in the
+// `[runtime]` section may set.
+fn runtime_options() -> Vec<String> {
+    let mut options = requirement_vars(&RuntimeOptions::requirements());
+    options.extend(requirement_vars(&BuilderOptions::requirements()));
+    options
+}
+
+impl Command {
+    /// Load the configuration file given to the command, and enable the
+    /// profiling and recording it requests, by setting environment variables.
+    ///
+    /// `sections` are the names of the runtime's hosts and backends, which
+    /// may each be configured by a section of the file, and `options` are the
+    /// environment variables they read, which the keys of those sections may
+    /// set.
+    ///
+    /// Must be called before the async runtime is built, while the process
+    /// has a single thread.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the configuration file cannot be loaded.
+    pub fn configure(&self, sections: &[&str], options: &[String]) -> Result<()> {
+        match self {
+            Self::Run {
+                config,
+                profile,
+                record,
+                ..
+            } => {
+                if let Some(config) = config {
+                    load_config(config, sections, options)?;
+                }
+                if let Some(profile) = profile {
+                    enable_profiling(profile);
+                }
+                if let Some(record) = record {
+                    enable_recording(record);
+                }
+            }
+            Self::Exec { config, .. } | Self::Replay { config, .. } => {
+                if let Some(config) = config {
+                    load_config(config, sections, options)?;
+                }
+            }
+            Self::Inspect { .. } => {}
+            #[cfg(feature = "jit")]
+            Self::Compile { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// Load configuration from the TOML file at `path` into the process
+/// environment.
+///
+/// Values are only set for environment variables that are not already set.
+/// Sections other than `[runtime]` must be named in `sections`, and their keys
+/// must set one of the environment variables in `options`.
+///
+/// Must be called before the async runtime is built, while the process has a
+/// single thread.
+///
+/// # Errors
+///
+/// Will fail if the file cannot be read, is not valid TOML, or is not
+/// structured as one section per host or backend containing only scalar (or
+/// array of scalar) values, or if it names an unknown section or option.
+pub fn load_config(path: &Path, sections: &[&str], options: &[String]) -> Result<()> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("reading config file {}", path.display()))?;
+    let vars = parse_config(&contents, sections, options)
+        .with_context(|| format!("invalid config file {}", path.display()))?;
+
+    for (name, value) in vars {
+        if env::var_os(&name).is_some() {
+            tracing::debug!("{name} set in environment, ignoring config file value");
+            continue;
+        }
+        // SAFETY: Environment variable modification is safe here because:
+        // 1. This runs before the async runtime is built, so no other thread
+        //    reads the environment concurrently
+        // 2. Backend clients that depend on these vars are created after this
+        unsafe {
+            env::set_var(name, value);
+        };
+    }
+
+    Ok(())
+}
+
+// Flatten the configuration file into environment variable names and values.
+fn parse_config(
+    contents: &str, sections: &[&str], options: &[String],
+) -> Result<Vec<(String, String)>> {
+    let table: Table = contents.parse()?;
+    let runtime_options = runtime_options();
+
+    let mut vars = Vec::new();
+    let mut sources = HashMap::new();
+
+    for (section, value) in table {
+        let Value::Table(keys) = value else {
+            bail!("`{section}` must be in a section, for example `[{RUNTIME_SECTION}]`");
+        };
+        check_name(&section).with_context(|| format!("section `[{section}]`"))?;
+        if section != RUNTIME_SECTION && !sections.contains(&section.as_str()) {
+            bail!(
+                "unknown section `[{section}]`: expected `[{RUNTIME_SECTION}]` or one of {}",
+                sections.iter().map(|s| format!("`[{s}]`")).collect::<Vec<_>>().join(", ")
+            );
+        }
+
+        for (key, value) in keys {
+            let path = format!("{section}.{key}");
+            check_name(&key).with_context(|| format!("key `{path}`"))?;
+            let value = to_env_value(&value).with_context(|| format!("key `{path}`"))?;
+
+            let name = if section == RUNTIME_SECTION {
+                key.to_uppercase()
+            } else {
+                format!("{section}_{key}").to_uppercase()
+            }
+            .replace('-', "_");
+            if section == RUNTIME_SECTION {
+                if !runtime_options.contains(&name) {
+                    bail!("unknown runtime option `{path}`: no runtime option is named {name}");
+                }
+            } else if !options.contains(&name) {
+                bail!(
+                    "unknown option `{path}`: none of the runtime's hosts or backends reads {name}"
+                );
+            }
+            if let Some(other) = sources.insert(name.clone(), path.clone()) {
+                bail!("`{other}` and `{path}` both set {name}");
+            }
+
+            vars.push((name, value));
+        }
+    }
+
+    Ok(vars)
+}
+
+// Section and key names must be usable as environment variable names.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        bail!("names may only contain ASCII letters, digits, `_` and `-`");
+    }
+    Ok(())
+}
+
+// Convert a TOML value to its environment variable representation. Arrays
+// are joined with commas.
+fn to_env_value(value: &Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        Value::Datetime(d) => Ok(d.to_string()),
+        Value::Array(values) => {
+            let values = values
+                .iter()
+                .map(|value| match value {
+                    Value::Array(_) | Value::Table(_) => {
+                        Err(anyhow!("arrays may only contain strings, numbers, or booleans"))
+                    }
+                    value => to_env_value(value),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(values.join(","))
+        }
+        Value::Table(_) => bail!("nested tables are not supported"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let vars = parse_config(
+            r#"
+            [runtime]
+            guest_timeout_ms = 5000
+            pooling_allocator = true
+
+            [http]
+            addr = "0.0.0.0:8080"
+
+            [kafka]
+            brokers = ["a:9092", "b:9092"]
+            "#,
+            &["http", "kafka"],
+            &["HTTP_ADDR".into(), "KAFKA_BROKERS".into()],
+        )
+        .expect("should parse");
+
+        let vars: HashMap<_, _> = vars.into_iter().collect();
+        assert_eq!(vars["GUEST_TIMEOUT_MS"], "5000");
+        assert_eq!(vars["POOLING_ALLOCATOR"], "true");
+        assert_eq!(vars["HTTP_ADDR"], "0.0.0.0:8080");
+        assert_eq!(vars["KAFKA_BROKERS"], "a:9092,b:9092");
+    }
+
+    #[test]
+    fn invalid() {
+        let options = ["SQL_DATABASE".to_string()];
+
+        let err = parse_config("addr = 1", &[], &[]).expect_err("should fail");
+        assert!(err.to_string().contains("must be in a section"));
+
+        let err =
+            parse_config("[sql.pool]\nsize = 1", &["sql"], &options).expect_err("should fail");
+        assert!(format!("{err:#}").contains("nested tables are not supported"));
+
+        let err =
+            parse_config("[guest]\ntimeout_ms = 1", &["sql"], &options).expect_err("should fail");
+        assert!(err.to_string().contains("unknown section `[guest]`"));
+
+        let err =
+            parse_config("[runtime]\nguest_timout_ms = 1", &[], &[]).expect_err("should fail");
+        assert!(err.to_string().contains("unknown runtime option"));
+
+        let err = parse_config("[sql]\ndatabse = \"app.db\"", &["sql"], &options)
+            .expect_err("should fail");
+        assert!(err.to_string().contains("unknown option `sql.databse`"));
+
+        let err = parse_config("[runtime]\nexec-timeout-ms = 1\nexec_timeout_ms = 2", &[], &[])
+            .expect_err("should fail");
+        assert!(err.to_string().contains("both set EXEC_TIMEOUT_MS"));
+    }
+
+    // the runtime options table documents every runtime option
+    #[test]
+    fn documented_options() {
+        let docs = include_str!("../../../docs/Architecture.md");
+        let table = docs.split("### Runtime Options").nth(1).expect("should document options");
+        let mut documented: Vec<&str> = table
+            .lines()
+            .skip_while(|line| !line.starts_with('|'))
+            .take_while(|line| line.starts_with('|'))
+            .filter_map(|line| line.split('`').nth(1))
+            .filter(|name| name.chars().all(|c| c.is_ascii_uppercase() || c == '_'))
+            .collect();
+
+        documented.sort_unstable();
+        let mut options = runtime_options();
+        options.sort_unstable();
+        assert_eq!(documented, options);
+    }
+}
@@ -0,0 +1,161 @@
+//! # Backend Connections
+//!
+//! Connects backends when the runtime starts, retrying failed connections
+//! with exponential backoff so a backend that is briefly unavailable, for
+//! example during a rolling deploy, does not stop the runtime.
+//!
+//! Backends are connected in parallel. In lazy mode, the admin server starts
+//! before backends connect and reports the runtime as not ready until every
+//! backend has connected. Guest servers only start once every backend has
+//! connected, so until then their listeners refuse connections.
+
+#![allow(missing_docs)]
+// `Backend::connect` futures are not required to be `Send`
+#![allow(clippy::future_not_send)]
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use fromenv::FromEnv;
+
+use crate::shutdown::Shutdown;
+use crate::traits::Backend;
+
+/// Options used to connect backends.
+///
+/// Options are loaded from environment variables.
+#[derive(Debug, Clone, FromEnv)]
+pub struct BackendOptions {
+    /// The number of times a failed backend connection is retried before the
+    /// runtime exits. Ignored in lazy mode, where connections are retried
+    /// until they succeed.
+    #[env(from = "BACKEND_CONNECT_RETRIES", default = "5")]
+    pub retries: u32,
+
+    /// Delay, in milliseconds, before the first retry. The delay doubles with
+    /// each subsequent retry.
+    #[env(from = "BACKEND_CONNECT_BACKOFF_MS", default = "500")]
+    pub backoff_ms: u64,
+
+    /// The maximum delay, in milliseconds, between retries.
+    #[env(from = "BACKEND_CONNECT_MAX_BACKOFF_MS", default = "30000")]
+    pub max_backoff_ms: u64,
+
+    /// Start the admin server before backends connect, reporting the runtime
+    /// as not ready until they do. Guest servers start once every backend
+    /// has connected.
+    #[env(from = "BACKEND_CONNECT_LAZY", default = "false")]
+    pub lazy: bool,
+}
+
+impl crate::FromEnv for BackendOptions {
+    fn from_env() -> Result<Self> {
+        Self::from_env().finalize().context("issue loading backend options")
+    }
+}
+
+impl BackendOptions {
+    /// The delay before retry `attempt`, counting from 1.
+    #[must_use]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor).min(self.max_backoff_ms))
+    }
+}
+
+/// Connect to the backend `B`, named `name`, retrying failed connections with
+/// backoff.
+///
+/// # Errors
+///
+/// Will fail once retries are exhausted, or if `shutdown` is signalled before
+/// the backend connects.
+pub async fn connect_backend<B: Backend>(
+    name: &str, options: &BackendOptions, shutdown: &Shutdown,
+) -> Result<B> {
+    let mut attempt = 0;
+    loop {
+        let e = match B::connect().await {
+            Ok(backend) => {
+                if attempt > 0 {
+                    tracing::info!(backend = name, "backend connected after {attempt} retries");
+                }
+                return Ok(backend);
+            }
+            Err(e) => e,
+        };
+
+        attempt += 1;
+        if !options.lazy && attempt > options.retries {
+            return Err(e.context(format!("connecting {name} after {} retries", options.retries)));
+        }
+        let delay = options.backoff(attempt);
+        tracing::warn!(backend = name, attempt, "issue connecting, retrying in {delay:?}: {e:#}");
+
+        tokio::select! {
+            () = tokio::time::sleep(delay) => {}
+            () = shutdown.signalled() => bail!("shutdown while connecting {name}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+
+    // Fails to connect until the third attempt.
+    #[derive(Debug)]
+    struct Flaky;
+
+    impl crate::FromEnv for Flaky {
+        fn from_env() -> Result<Self> {
+            Ok(Self)
+        }
+    }
+
+    impl Backend for Flaky {
+        type ConnectOptions = Self;
+
+        async fn connect_with(_: Self) -> Result<Self> {
+            if ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 2 {
+                bail!("connection refused");
+            }
+            Ok(Self)
+        }
+    }
+
+    fn options(retries: u32) -> BackendOptions {
+        BackendOptions {
+            retries,
+            backoff_ms: 1,
+            max_backoff_ms: 4,
+            lazy: false,
+        }
+    }
+
+    #[test]
+    fn backoff() {
+        let options = options(5);
+        assert_eq!(options.backoff(1), Duration::from_millis(1));
+        assert_eq!(options.backoff(3), Duration::from_millis(4));
+        assert_eq!(options.backoff(40), Duration::from_millis(4));
+    }
+
+    #[tokio::test]
+    async fn retries() {
+        let shutdown = Shutdown::new();
+
+        ATTEMPTS.store(0, Ordering::SeqCst);
+        connect_backend::<Flaky>("flaky", &options(1), &shutdown)
+            .await
+            .expect_err("should exhaust retries");
+
+        ATTEMPTS.store(0, Ordering::SeqCst);
+        connect_backend::<Flaky>("flaky", &options(2), &shutdown).await.expect("should connect");
+        assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,219 @@
+//! # Guest Core Dumps
+//!
+//! When enabled, wasmtime captures a core dump whenever a guest traps. The
+//! dump is written to a directory, or to a blobstore container, as
+//! `<unix millis>-<component>-<id>.coredump`, where `id` is the request id
+//! of the invocation when known. Only the most recent dumps are kept.
+//!
+//! Core dumps can be inspected with `wasmgdb` or other tools supporting the
+//! [tool-conventions core dump format](https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md).
+
+#![allow(missing_docs)]
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use fromenv::FromEnv;
+use tokio::fs;
+use wasmtime::{Store, WasmCoreDump};
+
+use crate::options::RuntimeOptions;
+use crate::traits::FutureResult;
+
+const EXTENSION: &str = ".coredump";
+
+// Core dumps written by this process, distinguishing dumps without an id.
+static WRITTEN: AtomicU64 = AtomicU64::new(0);
+
+// The store for `GUEST_COREDUMP_CONTAINER`, registered by the blobstore host.
+static CONTAINER: OnceLock<Arc<dyn CoreDumpStore>> = OnceLock::new();
+
+/// Options used to capture guest core dumps.
+///
+/// Options are loaded from environment variables.
+#[derive(Debug, Clone, FromEnv)]
+pub struct CoreDumpOptions {
+    /// Directory core dumps are written to.
+    #[env(from = "GUEST_COREDUMP_DIR")]
+    pub dir: Option<PathBuf>,
+
+    /// Blobstore container core dumps are written to, in place of `dir`.
+    /// Requires the blobstore host.
+    #[env(from = "GUEST_COREDUMP_CONTAINER")]
+    pub container: Option<String>,
+
+    /// The number of core dumps kept. Older dumps are deleted.
+    #[env(from = "GUEST_COREDUMP_RETAIN", default = "20")]
+    pub retain: usize,
+}
+
+impl crate::FromEnv for CoreDumpOptions {
+    fn from_env() -> Result<Self> {
+        Self::from_env().finalize().context("issue loading core dump options")
+    }
+}
+
+impl CoreDumpOptions {
+    /// Whether core dumps are captured when guests trap.
+    #[must_use]
+    pub const fn enabled(&self) -> bool {
+        self.dir.is_some() || self.container.is_some()
+    }
+}
+
+/// Storage for guest core dumps.
+pub trait CoreDumpStore: Send + Sync {
+    /// The names of stored objects.
+    fn list(&self) -> FutureResult<Vec<String>>;
+
+    /// Store `data` as `name`.
+    fn write(&self, name: String, data: Vec<u8>) -> FutureResult<()>;
+
+    /// Delete the object `name`.
+    fn delete(&self, name: String) -> FutureResult<()>;
+}
+
+/// Register the store used when core dumps are written to a blobstore
+/// container.
+///
+/// Called by the blobstore host when the runtime starts. Only the first
+/// store registered is used.
+pub fn set_core_dump_container(store: Arc<dyn CoreDumpStore>) {
+    let _ = CONTAINER.set(store);
+}
+
+/// Write the core dump captured when a guest invocation of `component`
+/// failed with `error`, identified by `id`.
+///
+/// Does nothing unless core dumps are enabled and `error` is a trap. Older
+/// dumps are deleted once the dump is written, and any errors are logged.
+pub async fn save_core_dump<T: 'static>(
+    store: &mut Store<T>, error: &anyhow::Error, options: &RuntimeOptions, component: &str,
+    id: Option<&str>,
+) {
+    let options = &options.coredump;
+    let Some(core_dump) = error.downcast_ref::<WasmCoreDump>() else {
+        return;
+    };
+    let Some(target) = target(options) else {
+        return;
+    };
+
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let id = id.map_or_else(|| WRITTEN.fetch_add(1, Ordering::Relaxed).to_string(), sanitize);
+    let name = format!("{millis}-{}-{id}{EXTENSION}", sanitize(component));
+    let data = core_dump.serialize(store, component);
+
+    match write(target.as_ref(), name.clone(), data, options.retain).await {
+        Ok(()) => tracing::info!(component, "wrote guest core dump {name}"),
+        Err(e) => tracing::error!(component, "issue writing guest core dump {name}: {e:#}"),
+    }
+}
+
+// The store core dumps are written to.
+fn target(options: &CoreDumpOptions) -> Option<Arc<dyn CoreDumpStore>> {
+    if options.container.is_some() {
+        if let Some(container) = CONTAINER.get() {
+            return Some(Arc::clone(container));
+        }
+        tracing::warn!("core dump container set but no blobstore host is linked");
+    }
+    let dir = options.dir.clone()?;
+    Some(Arc::new(Directory(dir)))
+}
+
+// Write the core dump, then delete all but the `retain` most recent dumps.
+async fn write(
+    store: &dyn CoreDumpStore, name: String, data: Vec<u8>, retain: usize,
+) -> Result<()> {
+    store.write(name, data).await?;
+
+    // names start with a timestamp, so sort oldest first
+    let mut dumps: Vec<String> =
+        store.list().await?.into_iter().filter(|name| name.ends_with(EXTENSION)).collect();
+    dumps.sort();
+    let expired = dumps.len().saturating_sub(retain);
+    for name in dumps.into_iter().take(expired) {
+        store.delete(name).await?;
+    }
+    Ok(())
+}
+
+// Replace characters that are not safe in a file or object name.
+#[allow(clippy::redundant_pub_crate)] // not part of the glob re-exported API
+pub(crate) fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+// Core dumps written to a directory, using `tokio::fs` so writing and pruning
+// dumps does not block the async runtime.
+struct Directory(PathBuf);
+
+impl CoreDumpStore for Directory {
+    fn list(&self) -> FutureResult<Vec<String>> {
+        let dir = self.0.clone();
+        Box::pin(async move {
+            let mut entries =
+                fs::read_dir(&dir).await.with_context(|| format!("reading {}", dir.display()))?;
+            let mut names = Vec::new();
+            while let Some(entry) = entries.next_entry().await? {
+                names.extend(entry.file_name().into_string().ok());
+            }
+            Ok(names)
+        })
+    }
+
+    fn write(&self, name: String, data: Vec<u8>) -> FutureResult<()> {
+        let dir = self.0.clone();
+        Box::pin(async move {
+            fs::create_dir_all(&dir)
+                .await
+                .with_context(|| format!("creating {}", dir.display()))?;
+            fs::write(dir.join(name), data).await.context("writing core dump")
+        })
+    }
+
+    fn delete(&self, name: String) -> FutureResult<()> {
+        let path = self.0.join(name);
+        Box::pin(async move {
+            fs::remove_file(&path).await.with_context(|| format!("deleting {}", path.display()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn retention() {
+        let dir = std::env::temp_dir().join(format!("qwasr-coredump-{}", std::process::id()));
+        let store = Directory(dir.clone());
+
+        for millis in 1..=4 {
+            let name = format!("{millis}-guest-{millis}{EXTENSION}");
+            write(&store, name, vec![0], 2).await.expect("should write");
+        }
+        fs::write(dir.join("notes.txt"), "kept").await.expect("should write");
+        write(&store, format!("5-guest-5{EXTENSION}"), vec![0], 2).await.expect("should write");
+
+        let mut names = store.list().await.expect("should list");
+        names.sort();
+        assert_eq!(
+            names,
+            [format!("4-guest-4{EXTENSION}"), format!("5-guest-5{EXTENSION}"), "notes.txt".into()]
+        );
+        fs::remove_dir_all(dir).await.expect("should clean up");
+    }
+
+    #[test]
+    fn sanitized() {
+        assert_eq!(sanitize("req/1 2"), "req_1_2");
+    }
+}
@@ -2,61 +2,121 @@
 
 use std::env;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, bail};
 use qwasr_otel::Telemetry;
 use tracing::instrument;
-use wasmtime::component::{Component, InstancePre, Linker};
-use wasmtime::{Config, Engine};
+use wasmtime::Engine;
+use wasmtime::component::{Component, Linker};
 use wasmtime_wasi::WasiView;
 
-use crate::traits::Host;
-
-/// Build the Wasmtime `Engine` and `Linker` for this runtime.
+use crate::engine::{config, linker, load_component};
+use crate::epoch;
+use crate::guests::{Guests, Routes};
+use crate::options::RuntimeOptions;
+use crate::policy::Policy;
+use crate::reload::{Guest, Reloader};
+use crate::traits::{FromEnv, Host};
+use crate::wasi::GuestWasi;
+
+/// Build the Wasmtime `Engine` and `Linker` for this runtime and compile the
+/// guest components it hosts.
+///
+/// Each component is named after its file name, without the extension.
 ///
 /// # Errors
 ///
-/// Will fail if the provided `wasm` file cannot be compiled/deserialized
-/// as a `Component` or the `Linker` cannot be initialized with WASI
+/// Will fail if no `wasm` files are given, a file cannot be compiled/deserialized as a
+/// `Component`, two components have the same name, the routes between
+/// components are invalid, or the `Linker` cannot be initialized with WASI
 /// support.
 #[instrument]
-pub fn create<T: WasiView + 'static>(wasm: &PathBuf) -> Result<Compiled<T>> {
-    init_env(wasm)?;
+pub fn create<T: WasiView + 'static>(wasm: &[PathBuf]) -> Result<Compiled<T>> {
+    let names = wasm.iter().map(|wasm| component_name(wasm)).collect::<Vec<_>>();
+    init_env(&names)?;
     tracing::info!("initializing runtime");
 
-    let mut config = Config::new();
-    config.async_support(true);
-    config.wasm_component_model_async(true);
-    let engine = Engine::new(&config)?;
-
-    // TODO: cause executing WebAssembly to periodically yield
-    //  1. enable `Config::epoch_interruption`
-    //  2. Set `Store::epoch_deadline_async_yield_and_update`
-    //  3. Call `Engine::increment_epoch` periodically
-
-    // SAFETY: The caller should ensure only valid pre-compiled wasm files are provided.
-    let component = unsafe { Component::deserialize_file(&engine, wasm) }.or_else(|e| {
-        if cfg!(feature = "jit") {
-            Component::from_file(&engine, wasm)
-        } else {
-            Err(anyhow!("Issue loading component: {e}. Enable `jit` feature to load wasm32 files."))
-        }
-    })?;
-
-    // register services with runtime's Linker
-    let mut linker = Linker::new(&engine);
-    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
-    wasmtime_wasi::p3::add_to_linker(&mut linker)?;
+    let options = <RuntimeOptions as FromEnv>::from_env()?;
+    let compiled = create_with(wasm, options)?;
 
     tracing::info!("runtime intialized");
+    Ok(compiled)
+}
+
+/// Build the Wasmtime `Engine` and `Linker` with `options` and compile the
+/// guest components, as for [`create`], without initializing telemetry.
+///
+/// Used to run guests in-process, for example by [`crate::testing`].
+///
+/// # Errors
+///
+/// Will fail for the same reasons as [`create`].
+pub fn create_with<T: WasiView + 'static>(
+    wasm: &[PathBuf], options: RuntimeOptions,
+) -> Result<Compiled<T>> {
+    if wasm.is_empty() {
+        bail!("no components to run");
+    }
+    let names = wasm.iter().map(|wasm| component_name(wasm)).collect::<Vec<_>>();
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            bail!("more than one component is named `{name}`: rename one of the files");
+        }
+    }
 
-    Ok(Compiled { component, linker })
+    let routes = Routes::parse(options.routes.as_deref(), &names)?;
+    let policy = options.policy_file.as_deref().map(Policy::load).transpose()?.unwrap_or_default();
+    let guest_wasi = GuestWasi::new(&options.wasi).context("preparing guest WASI context")?;
+    let engine = Engine::new(&config(&options))?;
+
+    // cause executing WebAssembly to periodically yield
+    epoch::start_ticker(&engine, options.epoch_tick())?;
+
+    let components = wasm
+        .iter()
+        .zip(&names)
+        .map(|(wasm, name)| {
+            let (component, version) = load_component(&engine, wasm, &options)
+                .with_context(|| format!("compiling {}", wasm.display()))?;
+            Ok(Loaded {
+                name: (*name).to_string(),
+                wasm: wasm.clone(),
+                component,
+                version,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // register services with runtime's Linker
+    let linker = linker(&engine)?;
+
+    Ok(Compiled {
+        components,
+        routes,
+        linker,
+        options,
+        policy: Arc::new(policy),
+        wasi: Arc::new(guest_wasi),
+    })
 }
 
-/// A compiled WebAssembly component with its associated Linker.
+/// Compiled WebAssembly components with their associated Linker.
 pub struct Compiled<T: WasiView + 'static> {
-    component: Component,
+    components: Vec<Loaded>,
+    routes: Routes,
     linker: Linker<T>,
+    options: RuntimeOptions,
+    policy: Arc<Policy>,
+    wasi: Arc<GuestWasi>,
+}
+
+// A compiled component, the file it was loaded from, and its version.
+struct Loaded {
+    name: String,
+    wasm: PathBuf,
+    component: Component,
+    version: Option<String>,
 }
 
 impl<T: WasiView> Compiled<T> {
@@ -69,14 +129,77 @@ impl<T: WasiView> Compiled<T> {
         H::add_to_linker(&mut self.linker)
     }
 
-    /// Pre-instantiate component.
+    /// Link a WASI component to the runtime using its `add_to_linker`
+    /// function, for hosts chosen at runtime.
     ///
     /// # Errors
     ///
-    /// Will fail if the component cannot be pre-instantiated.
-    pub fn pre_instantiate(&mut self) -> Result<InstancePre<T>> {
-        self.linker.instantiate_pre(&self.component)
+    /// Will fail if the host cannot be added to the Linker.
+    pub fn link_with(&mut self, add_to_linker: fn(&mut Linker<T>) -> Result<()>) -> Result<()> {
+        add_to_linker(&mut self.linker)
+    }
+
+    /// Pre-instantiate the components.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if a component cannot be pre-instantiated.
+    pub fn pre_instantiate(&mut self) -> Result<Guests<T>> {
+        let guests = self
+            .components
+            .iter()
+            .map(|loaded| {
+                let instance_pre = self
+                    .linker
+                    .instantiate_pre(&loaded.component)
+                    .with_context(|| format!("linking {}", loaded.wasm.display()))?;
+                Ok(Guest::new(&loaded.name, instance_pre, loaded.version.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Guests::new(self.linker.engine().clone(), guests, self.routes.clone()))
+    }
+
+    /// Returns a `Reloader` for each of the `guests` that replaces the
+    /// component with the component found at the path the runtime was
+    /// created with.
+    #[must_use]
+    pub fn reloaders(&self, guests: &Guests<T>) -> Vec<Reloader<T>> {
+        self.components
+            .iter()
+            .filter_map(|loaded| {
+                let guest = guests.get(&loaded.name)?;
+                Some(Reloader::new(
+                    loaded.wasm.clone(),
+                    self.linker.clone(),
+                    guest.clone(),
+                    self.options.clone(),
+                ))
+            })
+            .collect()
+    }
+
+    /// The options used to create the runtime.
+    #[must_use]
+    pub const fn options(&self) -> &RuntimeOptions {
+        &self.options
+    }
+
+    /// The capability policy applied to guests.
+    #[must_use]
+    pub fn policy(&self) -> Arc<Policy> {
+        Arc::clone(&self.policy)
     }
+
+    /// The guest WASI context prepared from the options.
+    #[must_use]
+    pub fn wasi(&self) -> Arc<GuestWasi> {
+        Arc::clone(&self.wasi)
+    }
+}
+
+// The name of the component loaded from `wasm`.
+fn component_name(wasm: &Path) -> &str {
+    wasm.file_stem().and_then(|s| s.to_str()).unwrap_or("unknown")
 }
 
 /// Initialize telemetry for the runtime.
@@ -84,8 +207,9 @@ impl<T: WasiView> Compiled<T> {
 /// # Errors
 ///
 /// Will fail if the telemetry cannot be initialized.
-fn init_env(wasm: &Path) -> Result<()> {
-    let name = wasm.file_stem().and_then(|s| s.to_str()).unwrap_or("unknown");
+fn init_env(names: &[&str]) -> Result<()> {
+    // a runtime hosting several components is named after the runtime
+    let name = if let [name] = names { name } else { "qwasr" };
 
     if env::var("COMPONENT").is_err() {
         // SAFETY: Environment variable modification is safe here because:
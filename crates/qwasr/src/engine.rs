@@ -0,0 +1,132 @@
+//! # Engine Configuration
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use wasmparser::{Parser, Payload};
+use wasmtime::component::{Component, Linker};
+use wasmtime::{Config, Engine, InstanceAllocationStrategy, PoolingAllocationConfig};
+use wasmtime_wasi::WasiView;
+
+use crate::options::RuntimeOptions;
+use crate::{cache, signature};
+
+/// Build the wasmtime `Config` used to compile and run components.
+///
+/// Components must be pre-compiled with the same configuration they are run
+/// with.
+pub fn config(options: &RuntimeOptions) -> Config {
+    let mut config = Config::new();
+    config.async_support(true);
+    config.wasm_component_model_async(true);
+    config.epoch_interruption(true);
+    config.consume_fuel(options.guest_fuel.is_some());
+    config.coredump_on_trap(options.coredump.enabled());
+
+    if options.pooling.enabled {
+        let pooling = &options.pooling;
+        let mut allocator = PoolingAllocationConfig::new();
+        allocator
+            .total_component_instances(pooling.total_component_instances)
+            .total_core_instances(pooling.total_core_instances)
+            .total_memories(pooling.total_memories)
+            .total_tables(pooling.total_tables);
+        if let Some(memory_bytes) = options.limits.memory_bytes {
+            allocator.max_memory_size(memory_bytes);
+        }
+        if let Some(table_elements) = options.limits.table_elements {
+            allocator.table_elements(table_elements);
+        }
+        config.allocation_strategy(InstanceAllocationStrategy::Pooling(allocator));
+    }
+
+    config
+}
+
+/// Load a serialized (pre-compiled) or standard WASI component from `wasm`,
+/// along with the version recorded in its metadata.
+///
+/// When trusted keys are configured, the component's signature is verified
+/// before it is compiled or deserialized.
+///
+/// The file is read into memory rather than mapped so it can be safely
+/// replaced while the runtime is running.
+pub fn load_component(
+    engine: &Engine, wasm: &Path, options: &RuntimeOptions,
+) -> Result<(Component, Option<String>)> {
+    let bytes = fs::read(wasm)?;
+    if let Some(trusted) = &options.trusted_keys {
+        signature::verify(wasm, &bytes, trusted)?;
+    }
+    Ok((cache::load(engine, options, &bytes)?, component_version(&bytes)))
+}
+
+/// Returns the version recorded in the top-level `version` custom section of
+/// a wasm component, as added by `wasm-tools metadata add --version`.
+///
+/// Pre-compiled components do not keep custom sections, so have no version.
+fn component_version(bytes: &[u8]) -> Option<String> {
+    if !Parser::is_component(bytes) {
+        return None;
+    }
+
+    // nested modules and components may carry their own versions
+    let mut depth = 0_usize;
+    for payload in Parser::new(0).parse_all(bytes) {
+        match payload.ok()? {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth = depth.saturating_sub(1),
+            Payload::CustomSection(section) if depth == 0 && section.name() == "version" => {
+                return str::from_utf8(section.data()).ok().map(ToString::to_string);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Create a `Linker` with the runtime's built-in WASI support.
+pub fn linker<T: WasiView + 'static>(engine: &Engine) -> Result<Linker<T>> {
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    wasmtime_wasi::p3::add_to_linker(&mut linker)?;
+    Ok(linker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY: &[u8] = b"\0asm\x0d\0\x01\0";
+
+    // Append a custom section named `name` holding `data` to `wasm`.
+    fn custom(wasm: &[u8], name: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes = wasm.to_vec();
+        bytes.extend([0, u8::try_from(1 + name.len() + data.len()).expect("should fit")]);
+        bytes.push(u8::try_from(name.len()).expect("should fit"));
+        bytes.extend(name.as_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn version() {
+        assert_eq!(component_version(EMPTY), None);
+        assert_eq!(
+            component_version(&custom(EMPTY, "version", b"1.2.3")).as_deref(),
+            Some("1.2.3")
+        );
+        assert_eq!(component_version(&custom(EMPTY, "name", b"guest")), None);
+
+        // a version recorded by a nested module is not the component's
+        let module = custom(b"\0asm\x01\0\0\0", "version", b"0.1.0");
+        let mut nested = EMPTY.to_vec();
+        nested.extend([1, u8::try_from(module.len()).expect("should fit")]);
+        nested.extend(&module);
+        assert_eq!(component_version(&nested), None);
+
+        // pre-compiled components are not wasm
+        assert_eq!(component_version(b"\x7fELF"), None);
+    }
+}
@@ -0,0 +1,51 @@
+//! # Epoch Interruption
+//!
+//! Periodically increments the `Engine` epoch so executing guests yield to
+//! the async executor, and traps guests that run past their deadline.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use wasmtime::{Engine, Store, StoreContextMut, Trap, UpdateDeadline};
+
+/// Start a background thread that increments the engine's epoch every
+/// `interval`.
+///
+/// The thread holds a weak reference to the engine and exits once the engine
+/// has been dropped.
+pub fn start_ticker(engine: &Engine, interval: Duration) -> Result<()> {
+    let weak = engine.weak();
+    thread::Builder::new().name("epoch-ticker".into()).spawn(move || {
+        while let Some(engine) = weak.upgrade() {
+            engine.increment_epoch();
+            drop(engine);
+            thread::sleep(interval);
+        }
+    })?;
+    Ok(())
+}
+
+/// Configure the store to yield on every epoch tick and to trap with
+/// [`Trap::Interrupt`] once `timeout` has elapsed.
+pub fn set_deadline<T: 'static>(store: &mut Store<T>, timeout: Option<Duration>) {
+    set_deadline_with(store, timeout, |_| {});
+}
+
+/// Configure the store as for [`set_deadline`], calling `on_tick` with the
+/// store on every epoch tick.
+pub fn set_deadline_with<T: 'static>(
+    store: &mut Store<T>, timeout: Option<Duration>,
+    mut on_tick: impl FnMut(&StoreContextMut<'_, T>) + Send + Sync + 'static,
+) {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_callback(move |store| {
+        on_tick(&store);
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(Trap::Interrupt.into());
+        }
+        Ok(UpdateDeadline::Yield(1))
+    });
+}
@@ -0,0 +1,78 @@
+//! # Command Components
+//!
+//! Runs a guest exporting `wasi:cli/run` once, as a one-shot job, rather than
+//! serving requests. The guest is linked with the same hosts and backends as
+//! a server guest, so batch jobs can use keyvalue, sql, blobstore and the
+//! other hosts.
+//!
+//! Both `wasi:cli/run@0.3` and `wasi:cli/run@0.2` exports are supported.
+
+// `Server::run`-style futures are not required to be `Send`
+#![allow(clippy::future_not_send)]
+
+use std::process::ExitCode;
+
+use anyhow::{Context, Result, bail};
+use wasmtime_wasi::{I32Exit, WasiView, p2, p3};
+
+use crate::metrics::Invocation;
+use crate::traits::State;
+
+/// Run the command guest hosted by `state` once, passing `args` to the
+/// guest, and return the guest's exit code.
+///
+/// A guest returning an error from `run` exits with code `1`, and a guest
+/// calling `exit` exits with the code it passed. The run is limited by
+/// `EXEC_TIMEOUT_MS` rather than `GUEST_TIMEOUT_MS`.
+///
+/// # Errors
+///
+/// Will fail if the runtime does not host exactly one guest, the guest does
+/// not export `wasi:cli/run`, or the guest traps.
+pub async fn exec<S>(state: &S, args: &[String]) -> Result<ExitCode>
+where
+    S: State,
+    S::StoreCtx: WasiView,
+{
+    let [guest] = state.guests().all() else {
+        bail!("exactly one command component can be run");
+    };
+
+    // one-shot jobs run for as long as `EXEC_TIMEOUT_MS` allows, rather than
+    // the per-invocation deadline applied to servers
+    let mut store = state.new_store();
+    crate::epoch::set_deadline(&mut store, state.options().exec_timeout());
+    *store.data_mut().ctx().ctx = state.wasi().command_ctx(guest.name(), args);
+    let instance = match guest.instance_pre().instantiate_async(&mut store).await {
+        Ok(instance) => instance,
+        Err(e) => return Err(S::limiter(store.data_mut()).instantiate_error(e)),
+    };
+
+    let invocation = Invocation::start(guest.name(), "exec");
+    let result = if let Ok(command) = p3::bindings::Command::new(&mut store, &instance) {
+        store
+            .run_concurrent(async move |store| command.wasi_cli_run().call_run(store).await)
+            .await
+            .and_then(|result| result)
+    } else {
+        let command = p2::bindings::Command::new(&mut store, &instance)
+            .with_context(|| format!("{} does not export `wasi:cli/run`", guest.name()))?;
+        command.wasi_cli_run().call_run(&mut store).await
+    };
+    crate::record_fuel(&store, state.options(), guest.name(), "exec");
+    invocation.finish::<S>(&mut store, result.as_ref().err());
+    if let Err(e) = &result {
+        crate::save_core_dump(&mut store, e, state.options(), guest.name(), None).await;
+    }
+
+    match result {
+        Ok(Ok(())) => Ok(ExitCode::SUCCESS),
+        Ok(Err(())) => Ok(ExitCode::FAILURE),
+        Err(e) => match e.downcast_ref::<I32Exit>() {
+            Some(I32Exit(code)) => {
+                Ok(u8::try_from(*code).map_or(ExitCode::FAILURE, ExitCode::from))
+            }
+            None => Err(e.context(format!("running {}", guest.name()))),
+        },
+    }
+}
@@ -0,0 +1,243 @@
+//! # Guest Components
+//!
+//! A runtime can host several guest components, all sharing the runtime's
+//! backend connections. HTTP requests and messages are routed to components by
+//! the rules in `ROUTES`, each written `component=kind:pattern`:
+//!
+//! - `path:/orders` routes HTTP requests for `/orders` and any path below it.
+//! - `host:orders.example.com` routes HTTP requests for the host.
+//! - `topic:orders.*` routes messages for the topic. Topics ending in `*`
+//!   match every topic with that prefix.
+//!
+//! Host routes take precedence over path routes, and the longest matching
+//! path wins. A message is delivered to every component with a matching
+//! topic. When `ROUTES` is unset, a runtime hosting a single component routes
+//! every request and message to it.
+
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use wasmtime::Engine;
+
+use crate::reload::Guest;
+
+/// The guest components hosted by the runtime and the routes between them.
+///
+/// Cloned handles share the same components.
+pub struct Guests<T: 'static> {
+    engine: Engine,
+    all: Arc<[Guest<T>]>,
+    routes: Arc<Routes>,
+}
+
+impl<T: 'static> Guests<T> {
+    /// Create a new set of guests, compiled for `engine`, that requests and
+    /// messages are routed between using `routes`.
+    #[must_use]
+    pub fn new(engine: Engine, guests: Vec<Guest<T>>, routes: Routes) -> Self {
+        Self {
+            engine,
+            all: guests.into(),
+            routes: Arc::new(routes),
+        }
+    }
+
+    /// The engine the guests were compiled for.
+    #[must_use]
+    pub const fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// The hosted guests, in the order they were loaded.
+    #[must_use]
+    pub fn all(&self) -> &[Guest<T>] {
+        &self.all
+    }
+
+    /// The guest named `name`, if any.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Guest<T>> {
+        self.all.iter().find(|guest| guest.name() == name)
+    }
+
+    /// The guest an HTTP request for `host` and `path` is routed to, if any.
+    #[must_use]
+    pub fn route_http(&self, host: Option<&str>, path: &str) -> Option<&Guest<T>> {
+        self.routes.http(host, path).and_then(|name| self.get(name))
+    }
+
+    /// The guests a message published to `topic` is routed to.
+    #[must_use]
+    pub fn route_message(&self, topic: &str) -> Vec<&Guest<T>> {
+        self.routes.messaging(topic).into_iter().filter_map(|name| self.get(name)).collect()
+    }
+}
+
+impl<T: 'static> Clone for Guests<T> {
+    fn clone(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+            all: Arc::clone(&self.all),
+            routes: Arc::clone(&self.routes),
+        }
+    }
+}
+
+/// A rule matching HTTP requests or messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// HTTP requests for the path or any path below it.
+    Path(String),
+
+    /// HTTP requests for the host.
+    Host(String),
+
+    /// Messages published to the topic, or to any topic with the prefix when
+    /// ending in `*`.
+    Topic(String),
+}
+
+/// Routes from HTTP requests and messages to the components that handle
+/// them.
+#[derive(Debug, Clone, Default)]
+pub struct Routes {
+    routes: Vec<(String, Route)>,
+}
+
+impl Routes {
+    /// Parse the comma-separated `routes` between `components`.
+    ///
+    /// When `routes` is unset, a single component receives every request and
+    /// message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a route is malformed or names an unknown
+    /// component, or if `routes` is unset and there is more than one
+    /// component.
+    pub fn parse(routes: Option<&str>, components: &[&str]) -> Result<Self> {
+        let Some(routes) = routes else {
+            let [component] = components else {
+                bail!("`ROUTES` must be set to host more than one component");
+            };
+            return Ok(Self {
+                routes: vec![
+                    ((*component).to_string(), Route::Path("/".into())),
+                    ((*component).to_string(), Route::Topic("*".into())),
+                ],
+            });
+        };
+
+        let mut parsed = Vec::new();
+        for route in routes.split(',').map(str::trim).filter(|route| !route.is_empty()) {
+            let Some((component, rule)) = route.split_once('=') else {
+                bail!("invalid route `{route}`: expected `component=kind:pattern`");
+            };
+            if !components.contains(&component) {
+                bail!("invalid route `{route}`: no component named `{component}`");
+            }
+            let rule = match rule.split_once(':') {
+                Some(("path", path)) if path.starts_with('/') => Route::Path(path.into()),
+                Some(("host", host)) if !host.is_empty() => Route::Host(host.into()),
+                Some(("topic", topic)) if !topic.is_empty() => Route::Topic(topic.into()),
+                _ => bail!(
+                    "invalid route `{route}`: expected `path:/prefix`, `host:name`, or `topic:name`"
+                ),
+            };
+            parsed.push((component.to_string(), rule));
+        }
+
+        Ok(Self { routes: parsed })
+    }
+
+    /// The component an HTTP request for `host` and `path` is routed to, if
+    /// any.
+    #[must_use]
+    pub fn http(&self, host: Option<&str>, path: &str) -> Option<&str> {
+        let by_host = host.and_then(|host| {
+            self.routes.iter().find_map(|(component, route)| match route {
+                Route::Host(name) if name.eq_ignore_ascii_case(host) => Some(component.as_str()),
+                _ => None,
+            })
+        });
+
+        by_host.or_else(|| {
+            self.routes
+                .iter()
+                .filter_map(|(component, route)| match route {
+                    Route::Path(prefix) => {
+                        let prefix = prefix.trim_end_matches('/');
+                        let matched = path
+                            .strip_prefix(prefix)
+                            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+                        matched.then_some((prefix.len(), component.as_str()))
+                    }
+                    _ => None,
+                })
+                .max_by_key(|(len, _)| *len)
+                .map(|(_, component)| component)
+        })
+    }
+
+    /// The components a message published to `topic` is routed to.
+    #[must_use]
+    pub fn messaging(&self, topic: &str) -> Vec<&str> {
+        let mut components = Vec::new();
+        for (component, route) in &self.routes {
+            let Route::Topic(pattern) = route else {
+                continue;
+            };
+            let matched = pattern
+                .strip_suffix('*')
+                .map_or(pattern == topic, |prefix| topic.starts_with(prefix));
+            if matched && !components.contains(&component.as_str()) {
+                components.push(component.as_str());
+            }
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_component() {
+        let routes = Routes::parse(None, &["app"]).expect("should parse");
+        assert_eq!(routes.http(Some("example.com"), "/"), Some("app"));
+        assert_eq!(routes.http(None, "/orders/1"), Some("app"));
+        assert_eq!(routes.messaging("orders.created"), vec!["app"]);
+
+        Routes::parse(None, &["app", "other"]).expect_err("should require routes");
+    }
+
+    #[test]
+    fn routing() {
+        let routes = Routes::parse(
+            Some(
+                "web=path:/, orders=path:/orders, orders=topic:orders.*, admin=host:admin.local, \
+                 audit=topic:*",
+            ),
+            &["web", "orders", "admin", "audit"],
+        )
+        .expect("should parse");
+
+        assert_eq!(routes.http(Some("example.com"), "/"), Some("web"));
+        assert_eq!(routes.http(Some("example.com"), "/orders"), Some("orders"));
+        assert_eq!(routes.http(None, "/orders/1"), Some("orders"));
+        assert_eq!(routes.http(None, "/ordersx"), Some("web"));
+        assert_eq!(routes.http(Some("ADMIN.local"), "/orders"), Some("admin"));
+
+        assert_eq!(routes.messaging("orders.created"), vec!["orders", "audit"]);
+        assert_eq!(routes.messaging("billing"), vec!["audit"]);
+
+        let routes = Routes::parse(Some("orders=path:/orders"), &["orders"]).expect("should parse");
+        assert_eq!(routes.http(None, "/"), None);
+        assert!(routes.messaging("orders").is_empty());
+
+        Routes::parse(Some("nope=path:/"), &["web"]).expect_err("should reject unknown component");
+        Routes::parse(Some("web=path:orders"), &["web"]).expect_err("should reject relative path");
+        Routes::parse(Some("web=queue:orders"), &["web"]).expect_err("should reject unknown kind");
+    }
+}
@@ -0,0 +1,287 @@
+//! # Component Inspection
+//!
+//! Lists the WIT interfaces a component imports and exports, and compares its
+//! imports with the hosts linked into the runtime.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use serde::Serialize;
+use wasmtime::Engine;
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{Component, Linker, LinkerInstance, ResourceType};
+use wasmtime_wasi::WasiView;
+
+use crate::engine::{config, linker, load_component};
+use crate::options::RuntimeOptions;
+use crate::traits::FromEnv;
+
+// The name reported for imports provided by the runtime's built-in WASI
+// support.
+const WASI: &str = "wasi";
+
+// The name reported for imports that link without any implementation, such as
+// interfaces that only define types.
+const NOT_NEEDED: &str = "(not needed)";
+
+/// A host linked into the runtime: the host's name and the function used to
+/// add it to a `Linker`.
+pub type LinkHost<T> = (&'static str, fn(&mut Linker<T>) -> Result<()>);
+
+/// The result of inspecting a component against the hosts linked into the
+/// runtime.
+#[derive(Debug, Clone, Serialize)]
+pub struct Inspection {
+    /// The name of the component.
+    pub component: String,
+
+    /// The items imported by the component.
+    pub imports: Vec<Import>,
+
+    /// The items exported by the component.
+    pub exports: Vec<Export>,
+
+    /// Linked hosts that provide none of the component's imports.
+    pub unused_hosts: Vec<String>,
+}
+
+/// An item imported by a component.
+#[derive(Debug, Clone, Serialize)]
+pub struct Import {
+    /// The import name, for example `wasi:keyvalue/store@0.2.0-draft`.
+    pub name: String,
+
+    /// The kind of item imported, for example `instance` or `function`.
+    pub kind: &'static str,
+
+    /// The host providing the import, or `None` when no linked host does.
+    pub provided_by: Option<String>,
+
+    /// Why the import could not be linked, when no linked host provides it.
+    pub error: Option<String>,
+}
+
+/// An item exported by a component.
+#[derive(Debug, Clone, Serialize)]
+pub struct Export {
+    /// The export name, for example `wasi:http/handler@0.3.0-rc-2025-09-16`.
+    pub name: String,
+
+    /// The kind of item exported, for example `instance` or `function`.
+    pub kind: &'static str,
+}
+
+/// Inspect the component at `wasm`, comparing its imports with `hosts`.
+///
+/// # Errors
+///
+/// Will fail if the component cannot be loaded or the hosts cannot be
+/// linked.
+pub fn inspect<T: WasiView + 'static>(wasm: &Path, hosts: &[LinkHost<T>]) -> Result<Inspection> {
+    let options = <RuntimeOptions as FromEnv>::from_env()?;
+    let engine = Engine::new(&config(&options))?;
+    let (component, _) = load_component(&engine, wasm, &options)?;
+
+    let component_type = component.component_type();
+    let imports: Vec<(String, ComponentItem)> =
+        component_type.imports(&engine).map(|(name, item)| (name.to_string(), item)).collect();
+    let exports = component_type
+        .exports(&engine)
+        .map(|(name, item)| Export {
+            name: name.to_string(),
+            kind: kind(&item),
+        })
+        .collect();
+
+    // link every host except `skip`
+    let link = |skip: Option<usize>| -> Result<Linker<T>> {
+        let mut linker = linker(&engine)?;
+        for (index, (_, add_to_linker)) in hosts.iter().enumerate() {
+            if Some(index) != skip {
+                add_to_linker(&mut linker)?;
+            }
+        }
+        Ok(linker)
+    };
+
+    // an import is provided by a host when unlinking the host leaves the
+    // import unresolved
+    let unresolved_all = unresolved(link(None)?, &component, &imports, &engine)?;
+    let unresolved_none = unresolved(Linker::<T>::new(&engine), &component, &imports, &engine)?;
+    let mut provided_by = BTreeMap::new();
+    let mut unused_hosts = Vec::new();
+
+    for (index, (host, _)) in hosts.iter().enumerate() {
+        let unresolved_without = unresolved(link(Some(index))?, &component, &imports, &engine)?;
+        let provided: Vec<_> =
+            unresolved_without.keys().filter(|name| !unresolved_all.contains_key(*name)).collect();
+        if provided.is_empty() {
+            unused_hosts.push((*host).to_string());
+        }
+        for name in provided {
+            provided_by.insert(name.clone(), (*host).to_string());
+        }
+    }
+
+    let imports = imports
+        .iter()
+        .map(|(name, item)| {
+            let error = unresolved_all.get(name).cloned();
+            let provided_by = if error.is_some() {
+                None
+            } else {
+                let builtin = if unresolved_none.contains_key(name) { WASI } else { NOT_NEEDED };
+                Some(provided_by.get(name).cloned().unwrap_or_else(|| builtin.to_string()))
+            };
+            Import {
+                name: name.clone(),
+                kind: kind(item),
+                provided_by,
+                error,
+            }
+        })
+        .collect();
+
+    Ok(Inspection {
+        component: wasm.file_stem().and_then(|s| s.to_str()).unwrap_or("unknown").to_string(),
+        imports,
+        exports,
+        unused_hosts,
+    })
+}
+
+impl Inspection {
+    /// Returns the imports not provided by any linked host.
+    pub fn missing(&self) -> impl Iterator<Item = &Import> {
+        self.imports.iter().filter(|import| import.provided_by.is_none())
+    }
+
+    /// Print the inspection to stdout, as JSON when `json` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the component's imports is not provided by a
+    /// linked host, so the command fails in CI.
+    pub fn print(&self, json: bool) -> Result<()> {
+        if json {
+            println!("{}", serde_json::to_string_pretty(self)?);
+        } else {
+            print!("{self}");
+        }
+
+        let missing = self.missing().count();
+        if missing > 0 {
+            bail!("{missing} import(s) of {} are not provided by this runtime", self.component);
+        }
+        Ok(())
+    }
+}
+
+impl Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .imports
+            .iter()
+            .map(|i| i.name.len())
+            .chain(self.exports.iter().map(|e| e.name.len()))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(f, "component: {}", self.component)?;
+        writeln!(f, "\nimports:")?;
+        for import in &self.imports {
+            let provided_by = match (&import.provided_by, &import.error) {
+                (Some(host), _) => host.clone(),
+                (None, Some(error)) => format!("MISSING ({error})"),
+                (None, None) => "MISSING".to_string(),
+            };
+            writeln!(f, "  {:width$}  {:8}  {provided_by}", import.name, import.kind)?;
+        }
+        writeln!(f, "\nexports:")?;
+        for export in &self.exports {
+            writeln!(f, "  {:width$}  {}", export.name, export.kind)?;
+        }
+        if !self.unused_hosts.is_empty() {
+            writeln!(f, "\nunused hosts: {}", self.unused_hosts.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// Resolve the component's imports against `linker`, returning the name of
+// each import that could not be linked along with the reason.
+//
+// Unresolved imports are stubbed one at a time so every unresolved import is
+// reported, not just the first.
+fn unresolved<T: 'static>(
+    mut linker: Linker<T>, component: &Component, imports: &[(String, ComponentItem)],
+    engine: &Engine,
+) -> Result<BTreeMap<String, String>> {
+    linker.allow_shadowing(true);
+    let mut unresolved = BTreeMap::new();
+
+    for _ in 0..=imports.len() {
+        let Err(e) = linker.instantiate_pre(component) else {
+            return Ok(unresolved);
+        };
+
+        // the error names the import: "component imports instance `name`, ..."
+        let message = e.to_string();
+        let Some((name, item)) = message
+            .split('`')
+            .nth(1)
+            .and_then(|name| imports.iter().find(|(import, _)| import == name))
+        else {
+            return Err(e);
+        };
+        if unresolved.contains_key(name) {
+            return Err(e);
+        }
+
+        let reason = e.chain().skip(1).map(ToString::to_string).collect::<Vec<_>>().join(": ");
+        unresolved.insert(name.clone(), reason);
+        stub(&mut linker.root(), name, item, engine)?;
+    }
+
+    bail!("unable to resolve the imports of the component")
+}
+
+// Define an import with stubs so the remaining imports can be checked.
+fn stub<T: 'static>(
+    linker: &mut LinkerInstance<'_, T>, name: &str, item: &ComponentItem, engine: &Engine,
+) -> Result<()> {
+    match item {
+        ComponentItem::ComponentFunc(func) if func.async_() => linker
+            .func_new_concurrent(name, |_, _, _, _| {
+                Box::pin(async { bail!("import is not linked") })
+            }),
+        ComponentItem::ComponentFunc(_) => {
+            linker.func_new(name, |_, _, _, _| bail!("import is not linked"))
+        }
+        ComponentItem::ComponentInstance(instance) => {
+            let mut linker = linker.instance(name)?;
+            for (name, item) in instance.exports(engine) {
+                stub(&mut linker, name, &item, engine)?;
+            }
+            Ok(())
+        }
+        ComponentItem::Resource(_) => {
+            linker.resource(name, ResourceType::host::<()>(), |_, _| Ok(()))
+        }
+        _ => Ok(()),
+    }
+}
+
+const fn kind(item: &ComponentItem) -> &'static str {
+    match item {
+        ComponentItem::ComponentFunc(_) => "function",
+        ComponentItem::CoreFunc(_) => "core function",
+        ComponentItem::Module(_) => "module",
+        ComponentItem::Component(_) => "component",
+        ComponentItem::ComponentInstance(_) => "instance",
+        ComponentItem::Type(_) => "type",
+        ComponentItem::Resource(_) => "resource",
+    }
+}
@@ -0,0 +1,166 @@
+//! # Guest Instances
+//!
+//! Provides guest instances to servers, either by instantiating on demand or
+//! by checking out an instance from a pool of pre-instantiated (warm)
+//! instances.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+use wasmtime::Store;
+use wasmtime::component::{Instance, InstancePre};
+
+use crate::reload::Guest;
+use crate::traits::State;
+
+// A pre-instantiated guest, and the component and generation it was
+// instantiated from.
+type Warm<T> = (u64, InstancePre<T>, Store<T>, Instance);
+
+/// A source of guest instances for a single guest component.
+///
+/// When `WARM_INSTANCES` is non-zero, a background task keeps up to that many
+/// instances ready for use. Servers fall back to instantiating on demand
+/// whenever no warm instance is available.
+pub struct Instances<S: State> {
+    state: S,
+    guest: Guest<S::StoreCtx>,
+    warm: Option<Mutex<mpsc::Receiver<Warm<S::StoreCtx>>>>,
+}
+
+impl<S: State> Instances<S> {
+    /// Create a new instance source for the `guest` component, starting the
+    /// warm instance task when configured.
+    #[must_use]
+    pub fn new(state: &S, guest: &Guest<S::StoreCtx>) -> Arc<Self> {
+        let warm_instances = state.options().warm_instances;
+
+        let warm = (warm_instances > 0).then(|| {
+            let (sender, receiver) = mpsc::channel(warm_instances);
+            tokio::spawn(fill(state.clone(), guest.clone(), sender));
+            Mutex::new(receiver)
+        });
+
+        Arc::new(Self {
+            state: state.clone(),
+            guest: guest.clone(),
+            warm,
+        })
+    }
+
+    /// The guest component instances are provided for.
+    #[must_use]
+    pub const fn guest(&self) -> &Guest<S::StoreCtx> {
+        &self.guest
+    }
+
+    /// Returns a `Store` and guest `Instance` ready for a single invocation,
+    /// with the `InstancePre` the instance was created from.
+    ///
+    /// Callers should use the returned `InstancePre` rather than the guest's
+    /// current one, which may have been replaced by a reload since.
+    ///
+    /// Warm instances have their deadline and fuel budget reset on checkout.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the guest could not be instantiated.
+    pub async fn get(&self) -> Result<(Store<S::StoreCtx>, Instance, InstancePre<S::StoreCtx>)> {
+        let start = Instant::now();
+
+        let warm = self.warm.as_ref().and_then(|warm| {
+            // discard instances of a component that has since been reloaded
+            let generation = self.guest.generation();
+            let mut receiver = warm.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+            std::iter::from_fn(|| receiver.try_recv().ok()).find(|(g, ..)| *g == generation)
+        });
+
+        let (store, instance, instance_pre, is_warm) =
+            if let Some((_, instance_pre, mut store, instance)) = warm {
+                crate::epoch::set_deadline(&mut store, self.state.options().guest_timeout());
+                if let Some(fuel) = self.state.options().guest_fuel {
+                    store.set_fuel(fuel)?;
+                }
+                (store, instance, instance_pre, true)
+            } else {
+                let (_, instance_pre, store, instance) =
+                    instantiate(&self.state, &self.guest).await?;
+                (store, instance, instance_pre, false)
+            };
+
+        crate::metrics::record_instantiate(
+            start.elapsed(),
+            self.state.options(),
+            self.guest.name(),
+            is_warm,
+        );
+
+        Ok((store, instance, instance_pre))
+    }
+}
+
+async fn instantiate<S: State>(state: &S, guest: &Guest<S::StoreCtx>) -> Result<Warm<S::StoreCtx>> {
+    let (generation, instance_pre) = guest.current();
+    let mut store = state.new_store();
+    let instance = match instance_pre.instantiate_async(&mut store).await {
+        Ok(instance) => instance,
+        Err(e) => return Err(S::limiter(store.data_mut()).instantiate_error(e)),
+    };
+    Ok((generation, instance_pre, store, instance))
+}
+
+// Keep the warm instance channel full until the receiver is dropped.
+//
+// Failed pre-instantiations, for example when the pooling allocator is
+// briefly exhausted, are retried with backoff. Servers instantiate on demand
+// in the meantime.
+async fn fill<S: State>(
+    state: S, guest: Guest<S::StoreCtx>, sender: mpsc::Sender<Warm<S::StoreCtx>>,
+) {
+    let mut failures = 0;
+    loop {
+        let Ok(permit) = sender.reserve().await else {
+            return;
+        };
+        match instantiate(&state, &guest).await {
+            Ok(warm) => {
+                permit.send(warm);
+                failures = 0;
+            }
+            Err(e) => {
+                drop(permit);
+                failures += 1;
+                let delay = fill_backoff(failures);
+                tracing::error!(
+                    component = guest.name(),
+                    "issue pre-instantiating guest, retrying in {delay:?}: {e:?}"
+                );
+                tokio::select! {
+                    () = tokio::time::sleep(delay) => {}
+                    () = sender.closed() => return,
+                }
+            }
+        }
+    }
+}
+
+// The delay before pre-instantiating again after `failures` consecutive
+// failures, doubling from 100ms up to 30s.
+fn fill_backoff(failures: u32) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    Duration::from_millis(100).saturating_mul(factor).min(Duration::from_secs(30))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        assert_eq!(fill_backoff(1), Duration::from_millis(100));
+        assert_eq!(fill_backoff(3), Duration::from_millis(400));
+        assert_eq!(fill_backoff(40), Duration::from_secs(30));
+    }
+}
@@ -2,23 +2,71 @@
 
 #![cfg(not(target_arch = "wasm32"))]
 
+mod admin;
+mod builder;
+mod cache;
 #[cfg(feature = "jit")]
 mod compile;
+mod config;
+mod connect;
+mod coredump;
 mod create;
+mod engine;
+mod epoch;
+mod exec;
+mod guests;
+mod inspect;
+mod instances;
+mod limits;
+mod metrics;
+mod named;
+mod options;
+mod policy;
+mod profile;
+mod reload;
+mod services;
+mod shutdown;
+mod signature;
+mod supervise;
+pub mod testing;
+mod trace;
 mod traits;
+mod wasi;
 
 use std::path::PathBuf;
 
 pub use clap::Parser;
 use clap::Subcommand;
 pub use qwasr_runtime_macro::runtime;
-pub use {anyhow, futures, tokio, wasmtime, wasmtime_wasi};
+pub use {anyhow, futures, tokio, wasmtime, wasmtime_wasi, wasmtime_wasi_http};
 
 // re-export internal modules
+pub use self::admin::*;
+pub use self::builder::*;
 #[cfg(feature = "jit")]
 pub use self::compile::*;
+pub use self::config::*;
+pub use self::connect::*;
+pub use self::coredump::*;
 pub use self::create::*;
+pub use self::exec::*;
+pub use self::guests::*;
+pub use self::inspect::*;
+pub use self::instances::*;
+pub use self::limits::*;
+pub use self::metrics::{Invocation, record_fuel};
+pub use self::named::*;
+pub use self::options::*;
+pub use self::policy::*;
+pub use self::profile::*;
+pub use self::reload::*;
+pub use self::services::*;
+pub use self::shutdown::*;
+pub use self::signature::*;
+pub use self::supervise::*;
+pub use self::trace::*;
 pub use self::traits::*;
+pub use self::wasi::*;
 
 /// Command line interface for qwasr.
 #[derive(Parser, PartialEq, Eq)]
@@ -31,12 +79,73 @@ pub struct Cli {
 /// Subcommands for the qwasr CLI.
 #[derive(Subcommand, PartialEq, Eq)]
 pub enum Command {
-    /// Run the specified wasm guest.
+    /// Run the specified wasm guests.
     Run {
-        /// The path to the wasm file to run. The file can either be a
+        /// The paths to the wasm files to run. Each file can either be a
         /// serialized (pre-compiled) wasmtime `Component` or standard
-        /// WASI component
+        /// WASI component. Requests and messages are routed between
+        /// components using `ROUTES`.
+        #[arg(required = true)]
+        wasm: Vec<PathBuf>,
+
+        /// An optional TOML file configuring the runtime, hosts, and backends.
+        /// Environment variables override values in the file.
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// An optional directory to write guest profiles to. Sampled HTTP
+        /// requests and messages are profiled with wasmtime's guest profiler.
+        #[arg(long)]
+        profile: Option<PathBuf>,
+
+        /// An optional directory to write host call traces to. Each HTTP
+        /// request or message is recorded with the host calls it makes, so
+        /// it can be replayed with `replay`.
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+    /// Run the specified command component, exporting `wasi:cli/run`, once
+    /// and exit with its exit code.
+    Exec {
+        /// The path to the wasm file to run. The file can either be a
+        /// serialized (pre-compiled) wasmtime `Component` or standard WASI
+        /// component.
         wasm: PathBuf,
+
+        /// An optional TOML file configuring the runtime, hosts, and backends.
+        /// Environment variables override values in the file.
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Arguments passed to the guest, following `--`.
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Replay the guest invocation recorded in the specified trace, answering
+    /// host calls from the trace rather than backends.
+    Replay {
+        /// The path to the wasm file the invocation was recorded with. The
+        /// file can either be a serialized (pre-compiled) wasmtime `Component`
+        /// or standard WASI component.
+        wasm: PathBuf,
+
+        /// The path to the trace written by `run --record`.
+        trace: PathBuf,
+
+        /// An optional TOML file configuring the runtime, hosts, and backends.
+        /// Environment variables override values in the file.
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
+    /// List the specified component's imports and exports, and compare its
+    /// imports with the hosts linked into this runtime.
+    Inspect {
+        /// The path to the wasm file to inspect.
+        wasm: PathBuf,
+
+        /// Output the inspection as JSON.
+        #[arg(long)]
+        json: bool,
     },
     /// Compile the specified wasm32-wasip2 component.
     #[cfg(feature = "jit")]
@@ -44,9 +153,15 @@ pub enum Command {
         /// The path to the wasm file to compile.
         wasm: PathBuf,
 
-        /// An optional output directory. If not set, the compiled component
-        /// will be written to the same location as the input file.
+        /// An optional output file or directory. If not set, the compiled
+        /// component is written to stdout or, when signed, next to the input
+        /// file.
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        /// An optional PEM file holding an ed25519 private key used to sign
+        /// the compiled component.
+        #[arg(long)]
+        sign: Option<PathBuf>,
     },
 }
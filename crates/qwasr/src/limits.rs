@@ -0,0 +1,207 @@
+//! # Resource Limits
+//!
+//! Limits on the memories, tables, and instances a guest may create, enforced
+//! for each `Store` through wasmtime's [`ResourceLimiterAsync`]. The limiter
+//! also tracks the linear memory allocated by the store, reported as the
+//! store's memory high-water mark.
+
+#![allow(missing_docs)]
+
+use anyhow::{Context, Result, bail};
+use fromenv::FromEnv;
+use wasmtime::ResourceLimiterAsync;
+
+/// Resource limits applied to each guest `Store`.
+///
+/// Limits are loaded from environment variables. A guest that attempts to
+/// exceed a limit is trapped.
+#[derive(Debug, Clone, FromEnv)]
+pub struct GuestLimits {
+    /// The maximum size, in bytes, of any linear memory.
+    #[env(from = "GUEST_MAX_MEMORY_BYTES")]
+    pub memory_bytes: Option<usize>,
+
+    /// The maximum number of elements in any table.
+    #[env(from = "GUEST_MAX_TABLE_ELEMENTS")]
+    pub table_elements: Option<usize>,
+
+    /// The maximum number of instances that can be created in a store.
+    #[env(from = "GUEST_MAX_INSTANCES", default = "10000")]
+    pub instances: usize,
+
+    /// The maximum number of tables that can be created in a store.
+    #[env(from = "GUEST_MAX_TABLES", default = "10000")]
+    pub tables: usize,
+
+    /// The maximum number of linear memories that can be created in a store.
+    #[env(from = "GUEST_MAX_MEMORIES", default = "10000")]
+    pub memories: usize,
+}
+
+impl crate::FromEnv for GuestLimits {
+    fn from_env() -> Result<Self> {
+        Self::from_env().finalize().context("issue loading guest limits")
+    }
+}
+
+/// Enforces [`GuestLimits`] for a single guest `Store`, tracking the linear
+/// memory the store allocates.
+#[derive(Debug, Clone)]
+pub struct StoreLimiter {
+    limits: GuestLimits,
+    allocated: usize,
+    peak: usize,
+}
+
+impl StoreLimiter {
+    /// Create a limiter enforcing `limits`.
+    #[must_use]
+    pub const fn new(limits: GuestLimits) -> Self {
+        Self {
+            limits,
+            allocated: 0,
+            peak: 0,
+        }
+    }
+
+    /// The most bytes of linear memory allocated by the store at once.
+    #[must_use]
+    pub const fn memory_peak(&self) -> usize {
+        self.peak
+    }
+
+    /// Log and count `error` when it was returned because instantiating a
+    /// guest would exceed the store's instance, table, or memory count limit.
+    ///
+    /// Wasmtime checks these limits itself when instantiating, so they are
+    /// not reported to the limiter as growth is.
+    #[must_use]
+    pub fn instantiate_error(&self, error: anyhow::Error) -> anyhow::Error {
+        let counts = [
+            ("instance", "instances", self.limits.instances),
+            ("table", "tables", self.limits.tables),
+            ("memory", "memories", self.limits.memories),
+        ];
+        let message = error.to_string();
+        if let Some((_, resource, limit)) = counts.into_iter().find(|(desc, ..)| {
+            message.starts_with(&format!("resource limit exceeded: {desc} count too high"))
+        }) {
+            tracing::error!(monotonic_counter.resource_limit_exceeded = 1, resource, limit);
+        }
+        error
+    }
+}
+
+#[async_trait::async_trait]
+impl ResourceLimiterAsync for StoreLimiter {
+    async fn memory_growing(
+        &mut self, current: usize, desired: usize, _maximum: Option<usize>,
+    ) -> Result<bool> {
+        if let Some(limit) = self.limits.memory_bytes
+            && desired > limit
+        {
+            tracing::error!(
+                monotonic_counter.resource_limit_exceeded = 1,
+                resource = "memory",
+                current,
+                desired,
+                limit,
+            );
+            bail!("guest memory limit exceeded: {desired} bytes requested, limit is {limit}");
+        }
+
+        self.allocated += desired.saturating_sub(current);
+        self.peak = self.peak.max(self.allocated);
+        Ok(true)
+    }
+
+    async fn table_growing(
+        &mut self, current: usize, desired: usize, _maximum: Option<usize>,
+    ) -> Result<bool> {
+        if let Some(limit) = self.limits.table_elements
+            && desired > limit
+        {
+            tracing::error!(
+                monotonic_counter.resource_limit_exceeded = 1,
+                resource = "table",
+                current,
+                desired,
+                limit,
+            );
+            bail!("guest table limit exceeded: {desired} elements requested, limit is {limit}");
+        }
+        Ok(true)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(memories: usize) -> GuestLimits {
+        GuestLimits {
+            memory_bytes: None,
+            table_elements: None,
+            instances: 10,
+            tables: 10,
+            memories,
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_peak() {
+        let mut limiter = StoreLimiter::new(GuestLimits {
+            memory_bytes: Some(100),
+            ..limits(2)
+        });
+
+        assert!(limiter.memory_growing(0, 40, None).await.expect("should grow"));
+        assert!(limiter.memory_growing(0, 30, None).await.expect("should grow"));
+        assert!(limiter.memory_growing(40, 60, None).await.expect("should grow"));
+        limiter.memory_growing(60, 120, None).await.expect_err("should exceed limit");
+        assert_eq!(limiter.memory_peak(), 90);
+    }
+
+    #[cfg(feature = "jit")]
+    #[tokio::test]
+    async fn memory_count() {
+        use wasmtime::component::{Component, Linker};
+        use wasmtime::{Config, Engine, Store};
+
+        // a component instantiating a core module that defines one memory.
+        const ONE_MEMORY: &[u8] = b"\0asm\x0d\0\x01\0\
+            \x01\x0d\0asm\x01\0\0\0\x05\x03\x01\0\0\
+            \x02\x04\x01\0\0\0";
+
+        let mut config = Config::new();
+        config.async_support(true);
+        let engine = Engine::new(&config).expect("should create engine");
+        let component = Component::new(&engine, ONE_MEMORY).expect("should compile");
+        let instance_pre =
+            Linker::new(&engine).instantiate_pre(&component).expect("should pre-instantiate");
+
+        for (memories, allowed) in [(1, true), (0, false)] {
+            let mut store = Store::new(&engine, StoreLimiter::new(limits(memories)));
+            store.limiter_async(|limiter| limiter);
+            let result = instance_pre.instantiate_async(&mut store).await;
+            assert_eq!(result.is_ok(), allowed);
+
+            if let Err(e) = result {
+                let e = store.data().instantiate_error(e);
+                assert!(e.to_string().contains("memory count too high"));
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! # Runtime Metrics
+//!
+//! OpenTelemetry instruments recorded by the runtime for guest invocations.
+//! Metrics are exported by the meter provider installed by
+//! [`qwasr_otel::Telemetry`].
+//!
+//! Metrics are labelled with the `component` invoked, and invocation metrics
+//! with the `trigger` invoking it, for example `http` or `messaging`.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use opentelemetry::metrics::{Counter, Histogram, UpDownCounter};
+use opentelemetry::{KeyValue, global};
+use wasmtime::{Store, Trap};
+
+use crate::options::RuntimeOptions;
+use crate::traits::State;
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+struct Metrics {
+    fuel_consumed: Histogram<u64>,
+    instantiate_duration: Histogram<f64>,
+    invocation_duration: Histogram<f64>,
+    active_invocations: UpDownCounter<i64>,
+    traps: Counter<u64>,
+    memory_peak: Histogram<u64>,
+}
+
+fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(|| {
+        let meter = global::meter("qwasr");
+        Metrics {
+            fuel_consumed: meter
+                .u64_histogram("guest.fuel.consumed")
+                .with_description("Fuel consumed by a single guest invocation")
+                .with_unit("{fuel}")
+                .build(),
+            instantiate_duration: meter
+                .f64_histogram("guest.instantiate.duration")
+                .with_description("Time taken to provide a guest instance for an invocation")
+                .with_unit("s")
+                .build(),
+            invocation_duration: meter
+                .f64_histogram("guest.invocation.duration")
+                .with_description("Time taken by a guest to handle an invocation")
+                .with_unit("s")
+                .build(),
+            active_invocations: meter
+                .i64_up_down_counter("guest.invocations.active")
+                .with_description("Guest invocations in flight")
+                .with_unit("{invocation}")
+                .build(),
+            traps: meter
+                .u64_counter("guest.traps")
+                .with_description("Guest invocations ending in a trap, by trap code")
+                .with_unit("{trap}")
+                .build(),
+            memory_peak: meter
+                .u64_histogram("guest.memory.peak")
+                .with_description("Most linear memory allocated by a guest's store at once")
+                .with_unit("By")
+                .build(),
+        }
+    })
+}
+
+/// Record the fuel consumed by a guest invocation.
+///
+/// Does nothing when fuel metering is disabled. The `trigger` identifies the
+/// server that invoked the guest, for example `http` or `messaging`.
+pub fn record_fuel<T: 'static>(
+    store: &Store<T>, options: &RuntimeOptions, component: &str, trigger: &str,
+) {
+    let Some(budget) = options.guest_fuel else {
+        return;
+    };
+    let Ok(remaining) = store.get_fuel() else {
+        return;
+    };
+
+    metrics().fuel_consumed.record(
+        budget.saturating_sub(remaining),
+        &[
+            KeyValue::new("component", component.to_string()),
+            KeyValue::new("trigger", trigger.to_string()),
+        ],
+    );
+}
+
+/// Record the time taken to provide a guest instance for an invocation.
+///
+/// Instances are labelled by the allocator in use (`pooling` or `on-demand`)
+/// and whether the instance was checked out from the warm instance pool.
+pub fn record_instantiate(
+    duration: Duration, options: &RuntimeOptions, component: &str, warm: bool,
+) {
+    let allocator = if options.pooling.enabled { "pooling" } else { "on-demand" };
+
+    metrics().instantiate_duration.record(
+        duration.as_secs_f64(),
+        &[
+            KeyValue::new("component", component.to_string()),
+            KeyValue::new("allocator", allocator),
+            KeyValue::new("warm", warm),
+        ],
+    );
+}
+
+/// A guest invocation, counted as in flight until finished or dropped.
+///
+/// Started once the guest is instantiated, and finished once the guest has
+/// handled the invocation.
+pub struct Invocation {
+    start: Instant,
+    attributes: [KeyValue; 2],
+}
+
+impl Invocation {
+    /// Start an invocation of `component`, triggered by `trigger`.
+    #[must_use]
+    pub fn start(component: &str, trigger: &'static str) -> Self {
+        let attributes =
+            [KeyValue::new("component", component.to_string()), KeyValue::new("trigger", trigger)];
+        metrics().active_invocations.add(1, &attributes);
+        Self {
+            start: Instant::now(),
+            attributes,
+        }
+    }
+
+    /// Record the invocation's duration, the memory high-water mark of its
+    /// `store`, and the trap code when the invocation ended with `error`.
+    pub fn finish<S: State>(self, store: &mut Store<S::StoreCtx>, error: Option<&anyhow::Error>) {
+        let metrics = metrics();
+        metrics.invocation_duration.record(self.start.elapsed().as_secs_f64(), &self.attributes);
+
+        let peak = S::limiter(store.data_mut()).memory_peak();
+        metrics.memory_peak.record(u64::try_from(peak).unwrap_or(u64::MAX), &self.attributes);
+
+        if let Some(trap) = error.and_then(|e| e.downcast_ref::<Trap>()) {
+            let mut attributes = self.attributes.to_vec();
+            attributes.push(KeyValue::new("code", format!("{trap:?}")));
+            metrics.traps.add(1, &attributes);
+        }
+    }
+}
+
+impl Drop for Invocation {
+    fn drop(&mut self) {
+        metrics().active_invocations.add(-1, &self.attributes);
+    }
+}
@@ -0,0 +1,120 @@
+//! # Named Backends
+//!
+//! Binds several backends of one interface into a runtime, choosing between
+//! them by the name of the resource a guest opens. For example, keyvalue
+//! bucket `cache` can be held in memory while bucket `sessions` is held in
+//! Redis:
+//!
+//! ```rust,ignore
+//! qwasr::runtime!({
+//!     hosts: {
+//!         WasiKeyValue: {
+//!             "cache": KeyValueDefault,
+//!             "sessions-*": Redis,
+//!             "*": Redis,
+//!         },
+//!     }
+//! });
+//! ```
+//!
+//! A name is matched exactly before matching patterns ending in `*`, and the
+//! longest matching pattern wins. The pattern `*` matches every name, so
+//! provides the default backend. Names matching no pattern are rejected.
+
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+
+/// Implemented by hosts whose resources are opened by name, so can be
+/// provided by several backends chosen with [`NamedBackends`].
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot choose backends by the name of the resource opened",
+    note = "only hosts whose resources are opened by name, such as `WasiKeyValue` or `WasiSql`, can bind several backends"
+)]
+pub trait NamedHost {
+    /// The context trait implemented by the host's backends, as a trait
+    /// object.
+    type Ctx: ?Sized + Send + Sync + 'static;
+}
+
+/// Backends of one interface, chosen by the name of the resource opened.
+///
+/// Hosts implementing [`NamedHost`] implement their context trait for
+/// `NamedBackends<Self::Ctx>`, forwarding each call to the backend chosen
+/// for the name.
+pub struct NamedBackends<C: ?Sized> {
+    backends: Vec<(String, Arc<C>)>,
+}
+
+impl<C: ?Sized> NamedBackends<C> {
+    /// Create a set of backends, each bound to names matching its pattern.
+    #[must_use]
+    pub const fn new(backends: Vec<(String, Arc<C>)>) -> Self {
+        Self { backends }
+    }
+
+    /// The backend bound to `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no backend is bound to `name`.
+    pub fn get(&self, name: &str) -> Result<&C> {
+        let exact = self.backends.iter().find(|(pattern, _)| pattern == name);
+        let matched = exact.or_else(|| {
+            self.backends
+                .iter()
+                .filter(|(pattern, _)| {
+                    pattern.strip_suffix('*').is_some_and(|prefix| name.starts_with(prefix))
+                })
+                .max_by_key(|(pattern, _)| pattern.len())
+        });
+        matched
+            .map(|(_, backend)| backend.as_ref())
+            .ok_or_else(|| anyhow!("no backend is bound to `{name}`"))
+    }
+}
+
+impl<C: ?Sized> Clone for NamedBackends<C> {
+    fn clone(&self) -> Self {
+        Self {
+            backends: self.backends.clone(),
+        }
+    }
+}
+
+impl<C: ?Sized + Debug> Debug for NamedBackends<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.backends.iter().map(|(pattern, backend)| (pattern, backend)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get() {
+        let backends: NamedBackends<str> = NamedBackends::new(vec![
+            ("cache".into(), Arc::from("memory")),
+            ("sessions-*".into(), Arc::from("redis")),
+            ("sessions-eu-*".into(), Arc::from("redis-eu")),
+            ("cache*".into(), Arc::from("nats")),
+        ]);
+
+        assert_eq!(backends.get("cache").expect("should match"), "memory");
+        assert_eq!(backends.get("cache2").expect("should match"), "nats");
+        assert_eq!(backends.get("sessions-us-1").expect("should match"), "redis");
+        assert_eq!(backends.get("sessions-eu-1").expect("should match"), "redis-eu");
+        backends.get("orders").expect_err("should reject unbound name");
+
+        let backends: NamedBackends<str> = NamedBackends::new(vec![
+            ("*".into(), Arc::from("default")),
+            ("cache".into(), Arc::from("memory")),
+        ]);
+        assert_eq!(backends.get("orders").expect("should match"), "default");
+        assert_eq!(backends.get("cache").expect("should match"), "memory");
+    }
+}
@@ -0,0 +1,203 @@
+//! # Runtime Options
+//!
+//! Options used to configure the wasmtime `Engine` and the `Store` created
+//! for each guest invocation.
+
+#![allow(missing_docs)]
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use fromenv::FromEnv;
+
+use crate::connect::BackendOptions;
+use crate::coredump::CoreDumpOptions;
+use crate::limits::GuestLimits;
+use crate::profile::ProfileOptions;
+use crate::supervise::SupervisorOptions;
+use crate::trace::TraceOptions;
+use crate::wasi::WasiOptions;
+
+/// Options used to configure the runtime.
+///
+/// Options are loaded from environment variables.
+#[derive(Debug, Clone, FromEnv)]
+pub struct RuntimeOptions {
+    /// Interval, in milliseconds, between epoch ticks. Executing guests yield
+    /// to the async executor on each tick. Values below 1 are raised to 1.
+    #[env(from = "EPOCH_TICK_MS", default = "10")]
+    pub epoch_tick_ms: u64,
+
+    /// Wall-clock deadline, in milliseconds, for a single guest invocation.
+    /// Guests still executing after the deadline are trapped. Defaults to
+    /// 30000 (30 seconds). Set to `0` to disable the deadline.
+    #[env(from = "GUEST_TIMEOUT_MS", default = "30000")]
+    pub guest_timeout_ms: u64,
+
+    /// Wall-clock deadline, in milliseconds, for a command component run by
+    /// `qwasr exec`, in place of `GUEST_TIMEOUT_MS`. Set to `0`, the default,
+    /// to run without a deadline.
+    #[env(from = "EXEC_TIMEOUT_MS", default = "0")]
+    pub exec_timeout_ms: u64,
+
+    /// Fuel budget for a single guest invocation. When set, fuel metering is
+    /// enabled and guests that exhaust their budget are trapped.
+    #[env(from = "GUEST_FUEL")]
+    pub guest_fuel: Option<u64>,
+
+    /// Resource limits applied to each guest `Store`.
+    #[env(nested)]
+    pub limits: GuestLimits,
+
+    /// Options used to build each guest's WASI context.
+    #[env(nested)]
+    pub wasi: WasiOptions,
+
+    /// Options for the pooling instance allocator.
+    #[env(nested)]
+    pub pooling: PoolingOptions,
+
+    /// Options used to connect backends.
+    #[env(nested)]
+    pub backends: BackendOptions,
+
+    /// Options used to restart failed servers.
+    #[env(nested)]
+    pub servers: SupervisorOptions,
+
+    /// Options used to profile guests.
+    #[env(nested)]
+    pub profile: ProfileOptions,
+
+    /// Options used to capture guest core dumps.
+    #[env(nested)]
+    pub coredump: CoreDumpOptions,
+
+    /// Options used to record host calls.
+    #[env(nested)]
+    pub trace: TraceOptions,
+
+    /// The number of pre-instantiated guests each server keeps warm. Set to
+    /// `0` to instantiate guests on demand.
+    #[env(from = "WARM_INSTANCES", default = "0")]
+    pub warm_instances: usize,
+
+    /// Reload the guest component whenever its file changes.
+    #[env(from = "RELOAD_WATCH", default = "false")]
+    pub reload_watch: bool,
+
+    /// Interval, in milliseconds, between checks for changes to the guest
+    /// component file.
+    #[env(from = "RELOAD_POLL_MS", default = "1000")]
+    pub reload_poll_ms: u64,
+
+    /// Grace period, in milliseconds, for in-flight guest invocations to
+    /// complete once shutdown has been signalled.
+    #[env(from = "SHUTDOWN_GRACE_MS", default = "30000")]
+    pub shutdown_grace_ms: u64,
+
+    /// Address for the admin server's health and information endpoints. The
+    /// admin server is disabled when unset.
+    #[env(from = "ADMIN_ADDR")]
+    pub admin_addr: Option<String>,
+
+    /// Directory used to cache compiled components. Components are compiled
+    /// on every start when unset.
+    #[env(from = "COMPILE_CACHE_DIR")]
+    pub compile_cache_dir: Option<PathBuf>,
+
+    /// Comma-separated, base64-encoded ed25519 public keys. When set, only
+    /// components with a detached signature from one of these keys are
+    /// loaded.
+    #[env(from = "TRUSTED_KEYS")]
+    pub trusted_keys: Option<String>,
+
+    /// An optional TOML file limiting the named resources, such as keyvalue
+    /// buckets or messaging topics, guests may access.
+    #[env(from = "POLICY_FILE")]
+    pub policy_file: Option<PathBuf>,
+
+    /// Comma-separated routes from HTTP requests and messages to components,
+    /// each in the form `component=path:/prefix`, `component=host:name`, or
+    /// `component=topic:name`. Required when running more than one component.
+    #[env(from = "ROUTES")]
+    pub routes: Option<String>,
+}
+
+impl crate::FromEnv for RuntimeOptions {
+    fn from_env() -> Result<Self> {
+        Self::from_env().finalize().context("issue loading runtime options")
+    }
+}
+
+/// Options for wasmtime's pooling instance allocator.
+///
+/// The pooling allocator pre-allocates memory and table slots for guest
+/// instances, making instantiation significantly cheaper than with the default
+/// on-demand allocator.
+#[derive(Debug, Clone, FromEnv)]
+pub struct PoolingOptions {
+    /// Use the pooling instance allocator instead of the on-demand allocator.
+    #[env(from = "POOLING_ALLOCATOR", default = "false")]
+    pub enabled: bool,
+
+    /// The maximum number of concurrent component instances.
+    #[env(from = "POOLING_TOTAL_COMPONENT_INSTANCES", default = "1000")]
+    pub total_component_instances: u32,
+
+    /// The maximum number of concurrent core module instances.
+    #[env(from = "POOLING_TOTAL_CORE_INSTANCES", default = "1000")]
+    pub total_core_instances: u32,
+
+    /// The maximum number of concurrent linear memories.
+    #[env(from = "POOLING_TOTAL_MEMORIES", default = "1000")]
+    pub total_memories: u32,
+
+    /// The maximum number of concurrent tables.
+    #[env(from = "POOLING_TOTAL_TABLES", default = "1000")]
+    pub total_tables: u32,
+}
+
+impl RuntimeOptions {
+    /// The interval between epoch ticks, at least 1 millisecond so the
+    /// ticker does not spin.
+    #[must_use]
+    pub const fn epoch_tick(&self) -> Duration {
+        Duration::from_millis(if self.epoch_tick_ms == 0 { 1 } else { self.epoch_tick_ms })
+    }
+
+    /// The wall-clock deadline for a single guest invocation, if any.
+    #[must_use]
+    pub const fn guest_timeout(&self) -> Option<Duration> {
+        if self.guest_timeout_ms == 0 {
+            None
+        } else {
+            Some(Duration::from_millis(self.guest_timeout_ms))
+        }
+    }
+
+    /// The wall-clock deadline for a command component run, if any.
+    #[must_use]
+    pub const fn exec_timeout(&self) -> Option<Duration> {
+        if self.exec_timeout_ms == 0 {
+            None
+        } else {
+            Some(Duration::from_millis(self.exec_timeout_ms))
+        }
+    }
+
+    /// The grace period for in-flight guest invocations to complete on
+    /// shutdown.
+    #[must_use]
+    pub const fn shutdown_grace(&self) -> Duration {
+        Duration::from_millis(self.shutdown_grace_ms)
+    }
+
+    /// The interval between checks for changes to the guest component file,
+    /// if watching is enabled.
+    #[must_use]
+    pub const fn reload_watch(&self) -> Option<Duration> {
+        if self.reload_watch { Some(Duration::from_millis(self.reload_poll_ms)) } else { None }
+    }
+}
@@ -0,0 +1,202 @@
+//! # Capability Policy
+//!
+//! Limits the named resources a guest may open through each WASI interface,
+//! such as keyvalue buckets or messaging topics. The policy is loaded from a
+//! TOML file with one section per interface, listing the names allowed for
+//! each capability. For example:
+//!
+//! ```toml
+//! default = "deny"
+//!
+//! [keyvalue]
+//! buckets = ["cache", "session-*"]
+//!
+//! [messaging]
+//! topics = ["orders.*"]
+//! ```
+//!
+//! Names ending in `*` allow every name with that prefix. Capabilities not
+//! listed in the file are governed by `default`, which is `"allow"` when
+//! unset. An empty list denies every name.
+//!
+//! Only the capabilities the hosts check may be listed: `keyvalue.buckets`,
+//! `blobstore.containers`, `sql.connections`, `vault.lockers`,
+//! `identity.identities` and `messaging.topics`. Any other section or key is
+//! rejected so a misspelling cannot silently leave a capability unrestricted.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use toml::{Table, Value};
+
+/// The `(interface, capability)` pairs checked by the hosts.
+const CAPABILITIES: &[(&str, &str)] = &[
+    ("keyvalue", "buckets"),
+    ("blobstore", "containers"),
+    ("sql", "connections"),
+    ("vault", "lockers"),
+    ("identity", "identities"),
+    ("messaging", "topics"),
+];
+
+/// Names of the resources guests may open, by interface and capability.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    deny_by_default: bool,
+    rules: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+impl Policy {
+    /// Load the policy from the TOML file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the file cannot be read, is not valid TOML, or is not
+    /// structured as one section per interface containing arrays of names.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("reading policy file {}", path.display()))?;
+        contents.parse().with_context(|| format!("invalid policy file {}", path.display()))
+    }
+
+    /// Whether the guest may access the resource `name` through the
+    /// `capability` of `interface`. Denied access is logged.
+    #[must_use]
+    pub fn allows(&self, interface: &str, capability: &str, name: &str) -> bool {
+        let patterns = self.rules.get(interface).and_then(|rules| rules.get(capability));
+        let allowed = patterns.map_or(!self.deny_by_default, |patterns| {
+            patterns.iter().any(|pattern| {
+                pattern.strip_suffix('*').map_or(pattern == name, |prefix| name.starts_with(prefix))
+            })
+        });
+        if !allowed {
+            tracing::warn!(interface, capability, name, "access denied by capability policy");
+        }
+        allowed
+    }
+
+    /// Check the guest may access the resource `name` through the
+    /// `capability` of `interface`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the denied access when the policy does not
+    /// allow it.
+    pub fn check(&self, interface: &str, capability: &str, name: &str) -> Result<()> {
+        if !self.allows(interface, capability, name) {
+            bail!("access denied: {interface} {capability} `{name}` is not allowed by policy");
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Policy {
+    type Err = anyhow::Error;
+
+    fn from_str(contents: &str) -> Result<Self> {
+        let table: Table = contents.parse()?;
+
+        let mut policy = Self::default();
+        for (section, value) in table {
+            match (section.as_str(), value) {
+                ("default", Value::String(default)) => {
+                    policy.deny_by_default = match default.as_str() {
+                        "allow" => false,
+                        "deny" => true,
+                        _ => bail!("`default` must be \"allow\" or \"deny\", found \"{default}\""),
+                    };
+                }
+                (_, Value::Table(capabilities)) => {
+                    if !CAPABILITIES.iter().any(|(interface, _)| *interface == section) {
+                        bail!("unknown interface `[{section}]`, expected one of {}", known());
+                    }
+                    let mut rules = HashMap::new();
+                    for (capability, value) in capabilities {
+                        if !CAPABILITIES.contains(&(section.as_str(), capability.as_str())) {
+                            bail!(
+                                "unknown capability `{capability}` in section `[{section}]`, \
+                                 expected one of {}",
+                                known()
+                            );
+                        }
+                        let names = names(&value)
+                            .with_context(|| format!("`{capability}` in section `[{section}]`"))?;
+                        rules.insert(capability, names);
+                    }
+                    policy.rules.insert(section, rules);
+                }
+                _ => bail!("`{section}` must be a section, for example `[keyvalue]`"),
+            }
+        }
+
+        Ok(policy)
+    }
+}
+
+fn known() -> String {
+    CAPABILITIES
+        .iter()
+        .map(|(interface, capability)| format!("`{interface}.{capability}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn names(value: &Value) -> Result<Vec<String>> {
+    let Value::Array(values) = value else {
+        bail!("expected an array of names");
+    };
+    values
+        .iter()
+        .map(|value| match value {
+            Value::String(name) => Ok(name.clone()),
+            _ => bail!("expected a string, found {value}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows() {
+        let policy: Policy = r#"
+            [keyvalue]
+            buckets = ["cache", "session-*"]
+
+            [messaging]
+            topics = []
+        "#
+        .parse()
+        .expect("should parse");
+
+        assert!(policy.allows("keyvalue", "buckets", "cache"));
+        assert!(policy.allows("keyvalue", "buckets", "session-42"));
+        assert!(!policy.allows("keyvalue", "buckets", "cache2"));
+        assert!(!policy.allows("messaging", "topics", "orders"));
+        assert!(policy.allows("sql", "connections", "db"));
+        policy.check("keyvalue", "buckets", "other").expect_err("should deny");
+
+        let policy: Policy =
+            "default = \"deny\"\n[sql]\nconnections = [\"db\"]".parse().expect("should parse");
+        assert!(policy.allows("sql", "connections", "db"));
+        assert!(!policy.allows("keyvalue", "buckets", "cache"));
+
+        assert!(Policy::default().allows("vault", "lockers", "secrets"));
+    }
+
+    #[test]
+    fn invalid() {
+        "default = \"maybe\"".parse::<Policy>().expect_err("should reject default");
+        "buckets = [\"cache\"]".parse::<Policy>().expect_err("should reject top-level names");
+        "[keyvalue]\nbuckets = \"cache\"".parse::<Policy>().expect_err("should reject string");
+        "[keyvalue]\nbuckets = [1]".parse::<Policy>().expect_err("should reject integers");
+        "[keyvalue]\nbucket = [\"cache\"]".parse::<Policy>().expect_err("should reject capability");
+        "[key-value]\nbuckets = [\"cache\"]"
+            .parse::<Policy>()
+            .expect_err("should reject interface");
+        "[sql]\ntopics = [\"db\"]".parse::<Policy>().expect_err("should reject pair");
+    }
+}
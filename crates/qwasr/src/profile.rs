@@ -0,0 +1,189 @@
+//! # Guest Profiling
+//!
+//! Samples the guest's stack on every epoch tick using wasmtime's
+//! `GuestProfiler`, writing one profile per sampled invocation. Profiles use
+//! the Firefox profiler's processed profile format, so can be opened with
+//! <https://profiler.firefox.com/>.
+//!
+//! Profiling is enabled by `qwasr run --profile <dir>`, or by setting
+//! `GUEST_PROFILE_DIR`. Invocations are sampled per component: every
+//! `GUEST_PROFILE_EVERY`th invocation is profiled, and when
+//! `GUEST_PROFILE_WINDOW_SECS` is set, at most one invocation per window.
+
+#![allow(missing_docs)]
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use fromenv::FromEnv;
+use wasmtime::component::InstancePre;
+use wasmtime::{GuestProfiler, Store};
+
+use crate::reload::Guest;
+use crate::traits::State;
+
+// The number of invocations of a component, and when it was last profiled.
+type Sampled = (u64, Option<Instant>);
+
+static SAMPLED: LazyLock<Mutex<HashMap<String, Sampled>>> = LazyLock::new(Mutex::default);
+
+/// Options used to profile guests.
+///
+/// Options are loaded from environment variables.
+#[derive(Debug, Clone, FromEnv)]
+pub struct ProfileOptions {
+    /// Directory profiles are written to. Profiling is disabled when unset.
+    #[env(from = "GUEST_PROFILE_DIR")]
+    pub dir: Option<PathBuf>,
+
+    /// Profile every Nth invocation of each component.
+    #[env(from = "GUEST_PROFILE_EVERY", default = "1")]
+    pub every: u64,
+
+    /// Profile at most one invocation of each component in each window, in
+    /// seconds. Set to `0` to profile every sampled invocation.
+    #[env(from = "GUEST_PROFILE_WINDOW_SECS", default = "0")]
+    pub window_secs: u64,
+}
+
+impl crate::FromEnv for ProfileOptions {
+    fn from_env() -> Result<Self> {
+        Self::from_env().finalize().context("issue loading profile options")
+    }
+}
+
+impl ProfileOptions {
+    // Whether invocation `count`, counting from 1, of a component last
+    // profiled at `last` is sampled.
+    fn sampled(&self, count: u64, last: Option<Instant>) -> bool {
+        let window = Duration::from_secs(self.window_secs);
+        (count - 1).is_multiple_of(self.every.max(1))
+            && !(self.window_secs > 0 && last.is_some_and(|last| last.elapsed() < window))
+    }
+
+    // Count an invocation of `component`, returning the count when the
+    // invocation is sampled.
+    fn sample(&self, component: &str) -> Option<u64> {
+        let mut sampled = SAMPLED.lock().unwrap_or_else(PoisonError::into_inner);
+        let (count, last) = sampled.entry(component.to_string()).or_default();
+        *count += 1;
+        let sample = self.sampled(*count, *last).then(|| {
+            *last = Some(Instant::now());
+            *count
+        });
+        drop(sampled);
+        sample
+    }
+}
+
+/// Profile guest invocations, writing profiles to `dir`.
+///
+/// Used by `qwasr run --profile <dir>`, so must be called before the async
+/// runtime is built, while the process has a single thread.
+pub fn enable_profiling(dir: &Path) {
+    // SAFETY: Environment variable modification is safe here because it runs
+    // before the async runtime is built, so no other thread reads the
+    // environment concurrently, and before the runtime's options are loaded.
+    unsafe {
+        env::set_var("GUEST_PROFILE_DIR", dir);
+    };
+}
+
+/// A profile of a single guest invocation, written when finished.
+pub struct Profile {
+    profiler: Arc<Mutex<Option<GuestProfiler>>>,
+    path: PathBuf,
+}
+
+/// Start profiling the invocation of `guest` in `store`, instantiated from
+/// `instance_pre` and triggered by `trigger`, if profiling is enabled and the
+/// invocation is sampled.
+///
+/// Errors starting the profiler are logged and the invocation is not
+/// profiled.
+pub fn start_profile<S: State>(
+    state: &S, guest: &Guest<S::StoreCtx>, instance_pre: &InstancePre<S::StoreCtx>,
+    store: &mut Store<S::StoreCtx>, trigger: &str,
+) -> Option<Profile> {
+    let options = &state.options().profile;
+    let dir = options.dir.as_ref()?;
+
+    let count = options.sample(guest.name())?;
+
+    let interval = state.options().epoch_tick();
+    let component = instance_pre.component().clone();
+    let profiler =
+        match GuestProfiler::new_component(store.engine(), guest.name(), interval, component, []) {
+            Ok(profiler) => Arc::new(Mutex::new(Some(profiler))),
+            Err(e) => {
+                tracing::error!(component = guest.name(), "issue starting profiler: {e:#}");
+                return None;
+            }
+        };
+
+    let sampler = Arc::clone(&profiler);
+    crate::epoch::set_deadline_with(store, state.options().guest_timeout(), move |store| {
+        if let Some(profiler) = sampler.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+            profiler.sample(store, interval);
+        }
+    });
+
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let path = dir.join(format!("{}-{trigger}-{millis}-{count}.json", guest.name()));
+    Some(Profile { profiler, path })
+}
+
+impl Profile {
+    /// Stop profiling and write the profile, logging any error.
+    pub fn finish(self) {
+        let Some(profiler) = self.profiler.lock().unwrap_or_else(PoisonError::into_inner).take()
+        else {
+            return;
+        };
+        match self.write(profiler) {
+            Ok(()) => tracing::info!("wrote guest profile {}", self.path.display()),
+            Err(e) => tracing::error!("issue writing guest profile: {e:#}"),
+        }
+    }
+
+    fn write(&self, profiler: GuestProfiler) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        let file = File::create(&self.path)
+            .with_context(|| format!("creating {}", self.path.display()))?;
+        profiler.finish(BufWriter::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(every: u64, window_secs: u64) -> ProfileOptions {
+        ProfileOptions {
+            dir: Some(env::temp_dir()),
+            every,
+            window_secs,
+        }
+    }
+
+    #[test]
+    fn sampled() {
+        let every = options(3, 0);
+        assert!(every.sampled(1, None));
+        assert!(!every.sampled(2, None));
+        assert!(!every.sampled(3, None));
+        assert!(every.sampled(4, Some(Instant::now())));
+
+        let window = options(1, 60);
+        assert!(window.sampled(1, None));
+        assert!(!window.sampled(2, Some(Instant::now())));
+    }
+}
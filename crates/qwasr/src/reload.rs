@@ -0,0 +1,206 @@
+//! # Hot Reload
+//!
+//! Replaces the pre-instantiated guest component while the runtime is
+//! running. Guest invocations already in flight complete on the component
+//! they were instantiated from.
+
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use tokio::time;
+use wasmtime::component::{InstancePre, Linker};
+
+use crate::engine::load_component;
+use crate::options::RuntimeOptions;
+
+/// The pre-instantiated guest component used to instantiate new guests.
+///
+/// Cloned handles share the same component, so a component replaced through
+/// one handle is seen by all.
+pub struct Guest<T: 'static> {
+    name: Arc<str>,
+    current: Arc<RwLock<Current<T>>>,
+}
+
+struct Current<T: 'static> {
+    generation: u64,
+    instance_pre: InstancePre<T>,
+    version: Option<String>,
+}
+
+impl<T: 'static> Guest<T> {
+    /// Create a new guest named `name` from a pre-instantiated component and
+    /// the version recorded in its metadata.
+    #[must_use]
+    pub fn new(name: &str, instance_pre: InstancePre<T>, version: Option<String>) -> Self {
+        Self {
+            name: name.into(),
+            current: Arc::new(RwLock::new(Current {
+                generation: 0,
+                instance_pre,
+                version,
+            })),
+        }
+    }
+
+    /// The name of the guest component.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the current pre-instantiated component.
+    #[must_use]
+    pub fn instance_pre(&self) -> InstancePre<T> {
+        self.current.read().unwrap_or_else(PoisonError::into_inner).instance_pre.clone()
+    }
+
+    /// Returns the current pre-instantiated component along with its
+    /// generation.
+    #[must_use]
+    pub fn current(&self) -> (u64, InstancePre<T>) {
+        let current = self.current.read().unwrap_or_else(PoisonError::into_inner);
+        (current.generation, current.instance_pre.clone())
+    }
+
+    /// Returns the number of times the component has been replaced.
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.current.read().unwrap_or_else(PoisonError::into_inner).generation
+    }
+
+    /// Returns the version recorded in the current component's metadata, if
+    /// any.
+    #[must_use]
+    pub fn version(&self) -> Option<String> {
+        self.current.read().unwrap_or_else(PoisonError::into_inner).version.clone()
+    }
+
+    /// Atomically replace the pre-instantiated component and its version.
+    pub fn replace(&self, instance_pre: InstancePre<T>, version: Option<String>) {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        current.generation += 1;
+        current.instance_pre = instance_pre;
+        current.version = version;
+    }
+}
+
+impl<T: 'static> Clone for Guest<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: Arc::clone(&self.name),
+            current: Arc::clone(&self.current),
+        }
+    }
+}
+
+/// Reloads a guest component from disk.
+pub struct Reloader<T: 'static> {
+    wasm: PathBuf,
+    linker: Linker<T>,
+    guest: Guest<T>,
+    options: RuntimeOptions,
+}
+
+impl<T: 'static> Reloader<T> {
+    /// Create a reloader that loads the component at `wasm` as configured by
+    /// `options`, links it with `linker`, and replaces the `guest` component.
+    #[must_use]
+    pub const fn new(
+        wasm: PathBuf, linker: Linker<T>, guest: Guest<T>, options: RuntimeOptions,
+    ) -> Self {
+        Self {
+            wasm,
+            linker,
+            guest,
+            options,
+        }
+    }
+
+    /// Load the component from disk and replace the guest's pre-instantiated
+    /// component.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the component cannot be loaded or linked. The guest's
+    /// current component is kept.
+    pub fn reload(&self) -> Result<()> {
+        let (component, version) = load_component(self.linker.engine(), &self.wasm, &self.options)?;
+        let instance_pre = self.linker.instantiate_pre(&component)?;
+        self.guest.replace(instance_pre, version);
+        Ok(())
+    }
+
+    /// Start reloading the component whenever the process receives `SIGHUP`
+    /// and, when `watch` is set, whenever the component file changes.
+    pub fn start(self, watch: Option<Duration>) {
+        let reloader = Arc::new(self);
+
+        #[cfg(unix)]
+        tokio::spawn({
+            let reloader = Arc::clone(&reloader);
+            async move {
+                use tokio::signal::unix::{SignalKind, signal};
+
+                let mut hangup = match signal(SignalKind::hangup()) {
+                    Ok(hangup) => hangup,
+                    Err(e) => {
+                        tracing::error!("issue listening for SIGHUP: {e}");
+                        return;
+                    }
+                };
+                while hangup.recv().await.is_some() {
+                    tracing::info!("SIGHUP received, reloading component");
+                    reload_blocking(&reloader).await;
+                }
+            }
+        });
+
+        if let Some(interval) = watch {
+            tokio::spawn(async move {
+                let mut modified = reloader.modified();
+                let mut ticker = time::interval(interval);
+                loop {
+                    ticker.tick().await;
+                    let latest = reloader.modified();
+                    if latest != modified {
+                        modified = latest;
+                        tracing::info!("{} changed, reloading component", reloader.wasm.display());
+                        reload_blocking(&reloader).await;
+                    }
+                }
+            });
+        }
+    }
+
+    fn reload_logged(&self) {
+        match self.reload() {
+            Ok(()) => tracing::info!(
+                component = self.guest.name(),
+                generation = self.guest.generation(),
+                "reloaded {}",
+                self.wasm.display()
+            ),
+            Err(e) => tracing::error!(
+                monotonic_counter.component_reload_failed = 1,
+                component = self.guest.name(),
+                "issue reloading {}, keeping current component: {e:#}",
+                self.wasm.display()
+            ),
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.wasm.metadata().and_then(|metadata| metadata.modified()).ok()
+    }
+}
+
+// Compiling the component can take some time, so reload on the blocking pool.
+async fn reload_blocking<T: 'static>(reloader: &Arc<Reloader<T>>) {
+    let reloader = Arc::clone(reloader);
+    if let Err(e) = tokio::task::spawn_blocking(move || reloader.reload_logged()).await {
+        tracing::error!("issue reloading component: {e}");
+    }
+}
@@ -0,0 +1,134 @@
+//! # Runtime Services
+//!
+//! Connects a runtime's backends and starts its servers, or runs a guest once
+//! as a command or a replay. Shared by runtimes generated by the `runtime!`
+//! macro and those built with [`crate::RuntimeBuilder`], each of which
+//! provides its enabled hosts through [`Services`].
+//!
+//! Each function runs until the process receives `SIGTERM` or `SIGINT`, or
+//! its work completes, after which in-flight guest invocations are drained
+//! and telemetry is flushed.
+
+// `Backend::connect` and `Server::run` futures are not required to be `Send`
+#![allow(clippy::future_not_send)]
+
+use std::process::ExitCode;
+
+use anyhow::{Result, anyhow};
+use futures::future::{LocalBoxFuture, try_join_all};
+use wasmtime_wasi::WasiView;
+
+use crate::admin::serve_admin;
+use crate::exec::exec;
+use crate::shutdown::Shutdown;
+use crate::supervise::supervise;
+use crate::trace::Trace;
+use crate::traits::State;
+
+/// The enabled hosts of a runtime, with their backends and servers.
+pub trait Services: State {
+    /// Returns the names of the enabled hosts.
+    fn hosts(&self) -> &[&'static str];
+
+    /// Connect the backends of the enabled hosts in parallel, retrying failed
+    /// connections with backoff.
+    fn connect(&self, shutdown: &Shutdown) -> impl Future<Output = Result<()>>;
+
+    /// Run the server of the enabled host named `host`.
+    fn serve(&self, host: &'static str, shutdown: Shutdown) -> LocalBoxFuture<'_, Result<()>>;
+
+    /// Replay `trace` with the server of the enabled host named `host`,
+    /// returning `None` when the host did not record the trace.
+    fn replay<'a>(
+        &'a self, host: &'static str, trace: &'a Trace, shutdown: Shutdown,
+    ) -> LocalBoxFuture<'a, Option<Result<()>>>;
+}
+
+/// Connect backends and start the admin server and the server of each
+/// enabled host.
+///
+/// In lazy mode, the admin server starts before backends connect and the
+/// servers of the enabled hosts only once every backend has connected, so
+/// they do not listen, or consume messages, before then. A server that fails is restarted with
+/// backoff, and the runtime only exits once it has failed too often.
+///
+/// # Errors
+///
+/// Will fail if a backend cannot be connected, or a server fails too often.
+pub async fn start<S: Services>(state: &S) -> Result<()> {
+    with_shutdown(state, async |shutdown| {
+        let lazy = state.options().backends.lazy;
+        if !lazy {
+            state.connect(shutdown).await?;
+        }
+        let servers = async {
+            if lazy {
+                state.connect(shutdown).await?;
+            }
+            try_join_all(state.hosts().iter().map(|host| {
+                supervise(host, &state.options().servers, shutdown, || {
+                    state.serve(host, shutdown.clone())
+                })
+            }))
+            .await
+        };
+        tokio::try_join!(serve_admin(state, state.hosts(), shutdown.clone()), servers).map(|_| ())
+    })
+    .await
+}
+
+/// Connect backends, then run the command guest once, passing it `args`,
+/// without starting servers.
+///
+/// # Errors
+///
+/// Will fail if a backend cannot be connected, the guest cannot be run, or
+/// the process is interrupted.
+pub async fn run_command<S>(state: &S, args: &[String]) -> Result<ExitCode>
+where
+    S: Services,
+    S::StoreCtx: WasiView,
+{
+    with_shutdown(state, async |shutdown| {
+        state.connect(shutdown).await?;
+        tokio::select! {
+            result = exec(state, args) => result,
+            () = shutdown.signalled() => Err(anyhow!("interrupted")),
+        }
+    })
+    .await
+}
+
+/// Connect backends, then replay the invocation recorded in `trace` with the
+/// server that recorded it, without starting servers. Host calls are
+/// answered from the trace.
+///
+/// # Errors
+///
+/// Will fail if a backend cannot be connected, no enabled host replays the
+/// trace, or the replay fails.
+pub async fn replay<S: Services>(state: &S, trace: &Trace) -> Result<()> {
+    with_shutdown(state, async |shutdown| {
+        state.connect(shutdown).await?;
+        for host in state.hosts() {
+            if let Some(result) = state.replay(host, trace, shutdown.clone()).await {
+                return result;
+            }
+        }
+        Err(anyhow!("no enabled host replays `{}` invocations", trace.trigger))
+    })
+    .await
+}
+
+// Run `f` until it completes, then trigger shutdown and drain in-flight guest
+// invocations.
+async fn with_shutdown<S: State, T>(
+    state: &S, f: impl AsyncFnOnce(&Shutdown) -> Result<T>,
+) -> Result<T> {
+    let shutdown = Shutdown::on_signal();
+    let result = f(&shutdown).await;
+
+    shutdown.trigger();
+    shutdown.drain(state.options().shutdown_grace()).await;
+    result
+}
@@ -0,0 +1,113 @@
+//! # Graceful Shutdown
+//!
+//! Signals servers to stop accepting new work and tracks in-flight guest
+//! invocations so they can be drained before the runtime exits.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// A shutdown signal shared by the runtime's servers.
+///
+/// Servers should stop accepting connections, messages, or subscriptions once
+/// [`Shutdown::signalled`] resolves, and should spawn guest invocations using
+/// [`Shutdown::spawn`] so they are drained before the runtime exits.
+#[derive(Clone, Debug, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl Shutdown {
+    /// Create a new shutdown signal.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new shutdown signal that is triggered when the process
+    /// receives `SIGTERM` or `SIGINT`.
+    #[must_use]
+    pub fn on_signal() -> Self {
+        let shutdown = Self::new();
+        let token = shutdown.token.clone();
+
+        tokio::spawn(async move {
+            terminate().await;
+            tracing::info!("shutdown signal received");
+            token.cancel();
+        });
+
+        shutdown
+    }
+
+    /// Trigger shutdown.
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    /// Returns `true` once shutdown has been triggered.
+    #[must_use]
+    pub fn is_signalled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves once shutdown has been triggered.
+    pub async fn signalled(&self) {
+        self.token.cancelled().await;
+    }
+
+    /// Spawn a task that will be drained before the runtime exits.
+    pub fn spawn<F>(&self, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.tracker.spawn(task)
+    }
+
+    /// Wait for in-flight tasks to complete, up to `grace`, then flush
+    /// telemetry.
+    ///
+    /// Tasks still running after the grace period are abandoned.
+    pub async fn drain(&self, grace: Duration) {
+        self.tracker.close();
+
+        let in_flight = self.tracker.len();
+        if in_flight > 0 {
+            tracing::info!("draining {in_flight} in-flight tasks");
+        }
+        if tokio::time::timeout(grace, self.tracker.wait()).await.is_err() {
+            tracing::warn!(
+                "grace period elapsed, abandoning {} in-flight tasks",
+                self.tracker.len()
+            );
+        }
+
+        if let Err(e) = qwasr_otel::shutdown() {
+            tracing::error!("issue flushing telemetry: {e}");
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn terminate() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let Ok(mut sigterm) = signal(SignalKind::terminate()) else {
+        tracing::error!("issue listening for SIGTERM");
+        return tokio::signal::ctrl_c().await.unwrap_or_default();
+    };
+    tokio::select! {
+        _ = sigterm.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate() {
+    tokio::signal::ctrl_c().await.unwrap_or_default();
+}
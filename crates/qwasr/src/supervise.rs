@@ -0,0 +1,201 @@
+//! # Server Supervision
+//!
+//! Restarts a server that fails, rather than stopping every other server in
+//! the runtime with it. A server that returns before shutdown, for example
+//! when its subscription stream ends, has failed too. Failed servers are
+//! restarted with exponential
+//! backoff, and each restart is logged and counted by the `server_restarts`
+//! counter. The runtime only exits once a server has failed more than the
+//! configured number of times within the restart window.
+
+#![allow(missing_docs)]
+// `Server::run` futures are not required to be `Send`
+#![allow(clippy::future_not_send)]
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use fromenv::FromEnv;
+
+use crate::shutdown::Shutdown;
+
+/// Options used to restart failed servers.
+///
+/// Options are loaded from environment variables.
+#[derive(Debug, Clone, FromEnv)]
+pub struct SupervisorOptions {
+    /// The number of times a server may fail within the restart window
+    /// before the runtime exits.
+    #[env(from = "SERVER_MAX_RESTARTS", default = "5")]
+    pub max_restarts: usize,
+
+    /// The window, in seconds, over which server failures are counted.
+    #[env(from = "SERVER_RESTART_WINDOW_SECS", default = "300")]
+    pub restart_window_secs: u64,
+
+    /// Delay, in milliseconds, before the first restart. The delay doubles
+    /// with each failure within the restart window.
+    #[env(from = "SERVER_RESTART_BACKOFF_MS", default = "1000")]
+    pub restart_backoff_ms: u64,
+
+    /// The maximum delay, in milliseconds, before a restart.
+    #[env(from = "SERVER_RESTART_MAX_BACKOFF_MS", default = "30000")]
+    pub restart_max_backoff_ms: u64,
+}
+
+impl crate::FromEnv for SupervisorOptions {
+    fn from_env() -> Result<Self> {
+        Self::from_env().finalize().context("issue loading supervisor options")
+    }
+}
+
+impl SupervisorOptions {
+    /// The window over which server failures are counted.
+    #[must_use]
+    pub const fn restart_window(&self) -> Duration {
+        Duration::from_secs(self.restart_window_secs)
+    }
+
+    /// The delay before restarting a server that has failed `failures`
+    /// times within the restart window.
+    #[must_use]
+    pub fn backoff(&self, failures: usize) -> Duration {
+        let exponent = u32::try_from(failures.saturating_sub(1)).unwrap_or(u32::MAX);
+        let factor = 2u64.saturating_pow(exponent);
+        Duration::from_millis(
+            self.restart_backoff_ms.saturating_mul(factor).min(self.restart_max_backoff_ms),
+        )
+    }
+}
+
+/// Run the server named `name`, calling `run` again each time it fails or
+/// returns before `shutdown` is signalled.
+///
+/// Returns once the server returns successfully after `shutdown` is
+/// signalled, or `shutdown` is signalled while waiting to restart it.
+///
+/// # Errors
+///
+/// Will fail if the server fails more than `max_restarts` times within the
+/// restart window, or fails after `shutdown` is signalled.
+pub async fn supervise<F, Fut>(
+    name: &str, options: &SupervisorOptions, shutdown: &Shutdown, mut run: F,
+) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut failures = VecDeque::new();
+    loop {
+        let result = run().await;
+        if shutdown.is_signalled() {
+            return result.with_context(|| format!("{name} server failed while shutting down"));
+        }
+        let e = result.err().unwrap_or_else(|| anyhow!("server stopped before shutdown"));
+
+        let now = Instant::now();
+        failures.retain(|failed: &Instant| now.duration_since(*failed) < options.restart_window());
+        failures.push_back(now);
+        if failures.len() > options.max_restarts {
+            return Err(e.context(format!(
+                "{name} server failed {} times within {:?}",
+                failures.len(),
+                options.restart_window()
+            )));
+        }
+
+        let delay = options.backoff(failures.len());
+        tracing::error!(
+            monotonic_counter.server_restarts = 1,
+            server = name,
+            "server failed, restarting in {delay:?}: {e:#}"
+        );
+
+        tokio::select! {
+            () = tokio::time::sleep(delay) => {}
+            () = shutdown.signalled() => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+
+    use super::*;
+
+    fn options(max_restarts: usize) -> SupervisorOptions {
+        SupervisorOptions {
+            max_restarts,
+            restart_window_secs: 60,
+            restart_backoff_ms: 1,
+            restart_max_backoff_ms: 4,
+        }
+    }
+
+    #[tokio::test]
+    async fn restarts() {
+        let shutdown = Shutdown::new();
+
+        // fails twice, then runs until shutdown
+        let mut runs = 0;
+        supervise("flaky", &options(2), &shutdown, || {
+            runs += 1;
+            let failed = runs < 3;
+            let shutdown = shutdown.clone();
+            async move {
+                if failed {
+                    bail!("listener closed");
+                }
+                shutdown.trigger();
+                Ok(())
+            }
+        })
+        .await
+        .expect("should restart");
+        assert_eq!(runs, 3);
+
+        // fails more often than the threshold allows
+        let mut runs = 0;
+        supervise("failing", &options(2), &Shutdown::new(), || {
+            runs += 1;
+            async { bail!("listener closed") }
+        })
+        .await
+        .expect_err("should exceed threshold");
+        assert_eq!(runs, 3);
+    }
+
+    #[tokio::test]
+    async fn restarts_early_return() {
+        // returns before shutdown twice, then runs until shutdown
+        let shutdown = Shutdown::new();
+        let mut runs = 0;
+        supervise("stream", &options(2), &shutdown, || {
+            runs += 1;
+            let stopped = runs < 3;
+            let shutdown = shutdown.clone();
+            async move {
+                if !stopped {
+                    shutdown.trigger();
+                }
+                Ok(())
+            }
+        })
+        .await
+        .expect("should restart");
+        assert_eq!(runs, 3);
+
+        // keeps returning before shutdown
+        let mut runs = 0;
+        let e = supervise("stream", &options(2), &Shutdown::new(), || {
+            runs += 1;
+            async { Ok(()) }
+        })
+        .await
+        .expect_err("should exceed threshold");
+        assert_eq!(runs, 3);
+        assert!(format!("{e:#}").contains("stopped before shutdown"));
+    }
+}
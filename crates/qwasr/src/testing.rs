@@ -0,0 +1,174 @@
+//! # Testing Guests
+//!
+//! Runs guests in-process, linked with hosts backed by the `*Default`
+//! backends or by fakes written for the test, so guests can be tested
+//! without starting servers or connecting to real backends.
+//!
+//! A [`TestRuntime`] is driven directly: HTTP guests with
+//! `qwasr_wasi_http::handle`, messaging guests with
+//! `qwasr_wasi_messaging::deliver`, and command guests with [`crate::exec`].
+//! Results are returned as values rather than served over TCP.
+//!
+//! ```rust,ignore
+//! let runtime = TestRuntime::builder()
+//!     .host::<WasiHttp, _>(HttpDefault)
+//!     .host::<WasiKeyValue, _>(FakeKeyValue::with([("greeting", "hello")]))
+//!     .build("target/wasm32-wasip2/release/my_guest.wasm")?;
+//!
+//! let request = http::Request::post("/greet").body("world")?;
+//! let response = qwasr_wasi_http::handle(&runtime, request).await?;
+//! assert_eq!(response.status(), 200);
+//! ```
+//!
+//! Fakes are any type implementing the host's context trait, for example
+//! `WasiKeyValueCtx`, that can be cloned for each guest invocation.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use anyhow::Result;
+use wasmtime::component::Linker;
+
+use crate::builder::{DynamicHost, RuntimeCtx};
+use crate::create::create_with;
+use crate::guests::Guests;
+use crate::instances::Instances;
+use crate::limits::StoreLimiter;
+use crate::options::RuntimeOptions;
+use crate::policy::Policy;
+use crate::reload::Guest;
+use crate::traits::{FromEnv, Host, State};
+use crate::wasi::GuestWasi;
+
+// Creates the host context held by each guest invocation's store.
+type Context = Box<dyn Fn() -> Box<dyn Any + Send> + Send + Sync>;
+
+// Adds a host's functions to the linker.
+type AddToLinker = fn(&mut Linker<RuntimeCtx>) -> Result<()>;
+
+/// Builds a [`TestRuntime`] from hosts and the backends providing them.
+#[derive(Default)]
+pub struct TestBuilder {
+    hosts: Vec<(AddToLinker, Context)>,
+    options: Option<RuntimeOptions>,
+}
+
+impl TestBuilder {
+    /// Link the host `H`, provided by `backend`.
+    ///
+    /// `backend` is cloned for each guest invocation, so a fake backend
+    /// sharing its state between clones can be inspected once the guest has
+    /// run.
+    #[must_use]
+    pub fn host<H, B>(mut self, backend: B) -> Self
+    where
+        H: DynamicHost<B>,
+        B: Send + Sync + 'static,
+    {
+        let context: Context = Box::new(move || H::context(&backend));
+        self.hosts.push((<H as Host<RuntimeCtx>>::add_to_linker, context));
+        self
+    }
+
+    /// Use `options` rather than loading options from environment variables.
+    #[must_use]
+    pub fn options(mut self, options: RuntimeOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Compile the guest at `wasm` and link it with the hosts.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if options cannot be loaded, the guest cannot be compiled,
+    /// or the guest imports an interface no linked host provides.
+    pub fn build(self, wasm: impl AsRef<Path>) -> Result<TestRuntime> {
+        let options = match self.options {
+            Some(options) => options,
+            None => <RuntimeOptions as FromEnv>::from_env()?,
+        };
+
+        let mut compiled = create_with::<RuntimeCtx>(&[wasm.as_ref().to_path_buf()], options)?;
+        for (add_to_linker, _) in &self.hosts {
+            compiled.link_with(*add_to_linker)?;
+        }
+
+        Ok(TestRuntime {
+            guests: compiled.pre_instantiate()?,
+            options: compiled.options().clone(),
+            policy: compiled.policy(),
+            wasi: compiled.wasi(),
+            contexts: self.hosts.into_iter().map(|(_, context)| context).collect(),
+            instances: Arc::default(),
+        })
+    }
+}
+
+/// A runtime hosting a guest in-process, for tests.
+///
+/// Created with [`TestRuntime::builder`]. Each guest's instances are
+/// provided by a single source shared by every invocation, so warm instances
+/// are only filled once per runtime.
+#[derive(Clone)]
+pub struct TestRuntime {
+    guests: Guests<RuntimeCtx>,
+    options: RuntimeOptions,
+    policy: Arc<Policy>,
+    wasi: Arc<GuestWasi>,
+    contexts: Arc<[Context]>,
+    instances: Arc<OnceLock<HashMap<String, Arc<Instances<Self>>>>>,
+}
+
+impl TestRuntime {
+    /// Create a builder with no hosts linked.
+    #[must_use]
+    pub fn builder() -> TestBuilder {
+        TestBuilder::default()
+    }
+}
+
+impl State for TestRuntime {
+    type StoreCtx = RuntimeCtx;
+
+    fn store(&self) -> Self::StoreCtx {
+        let contexts = self.contexts.iter().map(|context| context()).collect();
+        RuntimeCtx::new(&self.options, Arc::clone(&self.policy), &self.wasi, contexts)
+    }
+
+    fn guests(&self) -> &Guests<Self::StoreCtx> {
+        &self.guests
+    }
+
+    fn options(&self) -> &RuntimeOptions {
+        &self.options
+    }
+
+    fn wasi(&self) -> &GuestWasi {
+        &self.wasi
+    }
+
+    fn limiter(ctx: &mut Self::StoreCtx) -> &mut StoreLimiter {
+        &mut ctx.limits
+    }
+
+    // Created on first use, from within the async runtime warm instances
+    // are filled on. The sources hold a copy of the runtime without them, so
+    // they are dropped with the runtime.
+    fn instances(&self, guest: &Guest<Self::StoreCtx>) -> Arc<Instances<Self>> {
+        let all = self.instances.get_or_init(|| {
+            let state = Self {
+                instances: Arc::default(),
+                ..self.clone()
+            };
+            self.guests
+                .all()
+                .iter()
+                .map(|guest| (guest.name().to_string(), Instances::new(&state, guest)))
+                .collect()
+        });
+        all.get(guest.name()).map_or_else(|| Instances::new(self, guest), Arc::clone)
+    }
+}
@@ -0,0 +1,436 @@
+//! # Host Call Traces
+//!
+//! Records the host calls made by a guest invocation, together with the
+//! request that triggered it, so a production invocation can be reproduced
+//! locally against the same component.
+//!
+//! Recording is enabled by `qwasr run --record <dir>`, or by setting
+//! `GUEST_TRACE_DIR`, and writes one trace per HTTP request or message.
+//! `qwasr replay <wasm> <trace>` re-runs the recorded invocation, answering
+//! each host call from the trace, in the order it was made, rather than
+//! calling backends.
+//!
+//! Hosts wrap calls to their backends with [`traced`] so they are recorded
+//! and replayed. Calls are traced by the key-value, SQL, messaging, and
+//! outbound HTTP hosts. Guests importing any other host with a backend, such
+//! as blobstore or vault, cannot be replayed, since their calls would reach
+//! real backends.
+
+#![allow(missing_docs)]
+
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow, bail};
+use fromenv::FromEnv;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::coredump::sanitize;
+use crate::guests::Guests;
+use crate::reload::Guest;
+use crate::traits::{FutureResult, State};
+
+// Interfaces of hosts whose calls to backends are not traced, by package.
+const UNTRACED: &[&str] =
+    &["wasi:blobstore/", "wasi:config/", "wasi:identity/", "wasi:vault/", "wasi:websockets/"];
+
+// Traces written by this process, distinguishing traces without an id.
+static RECORDED: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    // The tracer of the guest invocation running on the current task.
+    static TRACER: Tracer;
+}
+
+/// Options used to record host calls.
+///
+/// Options are loaded from environment variables.
+#[derive(Debug, Clone, FromEnv)]
+pub struct TraceOptions {
+    /// Directory traces are written to. Recording is disabled when unset.
+    #[env(from = "GUEST_TRACE_DIR")]
+    pub dir: Option<PathBuf>,
+}
+
+impl crate::FromEnv for TraceOptions {
+    fn from_env() -> Result<Self> {
+        Self::from_env().finalize().context("issue loading trace options")
+    }
+}
+
+/// Record the host calls of every guest invocation, writing traces to `dir`.
+///
+/// Used by `qwasr run --record <dir>`, so must be called before the async
+/// runtime is built, while the process has a single thread.
+pub fn enable_recording(dir: &Path) {
+    // SAFETY: Environment variable modification is safe here because it runs
+    // before the async runtime is built, so no other thread reads the
+    // environment concurrently, and before the runtime's options are loaded.
+    unsafe {
+        std::env::set_var("GUEST_TRACE_DIR", dir);
+    };
+}
+
+/// A recorded guest invocation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Trace {
+    /// The component invoked.
+    pub component: String,
+
+    /// The server that invoked the guest, such as `http` or `messaging`.
+    pub trigger: String,
+
+    /// The request the guest was invoked with, as recorded by the server.
+    pub request: Value,
+
+    /// The host calls made by the guest, in the order they were made.
+    pub calls: Vec<HostCall>,
+}
+
+/// A host call made by a guest, and its result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostCall {
+    /// The host function called, such as `keyvalue.get`.
+    pub call: String,
+
+    /// The arguments of the call.
+    pub args: Value,
+
+    /// The value returned, or the error's message.
+    pub result: Result<Value, String>,
+}
+
+impl Trace {
+    /// Load the trace written to `path` by a recording.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the file cannot be read or is not a trace.
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        serde_json::from_slice(&json).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// The request the guest was invoked with.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if the request was not recorded as an `R`.
+    pub fn request<R: DeserializeOwned>(&self) -> Result<R> {
+        serde_json::from_value(self.request.clone()).context("invalid request in trace")
+    }
+
+    /// The guest the trace was recorded with: the guest of the same name, or
+    /// the only guest.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if no guest is named for the component and there is not
+    /// exactly one guest, or if the guest imports a host whose calls are not
+    /// traced.
+    pub fn guest<'a, T>(&self, guests: &'a Guests<T>) -> Result<&'a Guest<T>> {
+        let ((Some(guest), _) | (None, [guest])) = (guests.get(&self.component), guests.all())
+        else {
+            bail!("no component named `{}` to replay", self.component);
+        };
+
+        let instance_pre = guest.instance_pre();
+        let component = instance_pre.component();
+        let component_type = component.component_type();
+        let untraced = untraced(component_type.imports(component.engine()).map(|(name, _)| name));
+        if !untraced.is_empty() {
+            bail!(
+                "cannot replay `{}`: calls to {} are not traced, so would reach real backends",
+                guest.name(),
+                untraced.join(", ")
+            );
+        }
+        Ok(guest)
+    }
+}
+
+// The imports of hosts whose calls are not traced.
+fn untraced<'a>(imports: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    imports.filter(|name| UNTRACED.iter().any(|package| name.starts_with(package))).collect()
+}
+
+/// Records or replays the host calls of a single guest invocation.
+#[derive(Clone)]
+pub struct Tracer {
+    tape: Arc<Mutex<Tape>>,
+    mode: Mode,
+}
+
+#[derive(Clone)]
+enum Mode {
+    // write the trace to the path once finished
+    Record(PathBuf),
+    Replay,
+}
+
+// The trace, and the next call to replay.
+#[derive(Default)]
+struct Tape {
+    trace: Trace,
+    next: usize,
+}
+
+/// Start recording the invocation of `component`, triggered by `trigger` and
+/// identified by `id`, if recording is enabled.
+#[must_use]
+pub fn start_recording<S: State>(
+    state: &S, component: &str, trigger: &str, id: Option<&str>,
+) -> Option<Tracer> {
+    let dir = state.options().trace.dir.as_ref()?;
+
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let id = id.map_or_else(|| RECORDED.fetch_add(1, Ordering::Relaxed).to_string(), sanitize);
+    let path = dir.join(format!("{}-{trigger}-{millis}-{id}.json", sanitize(component)));
+    let trace = Trace {
+        component: component.to_string(),
+        trigger: trigger.to_string(),
+        ..Trace::default()
+    };
+    Some(Tracer::new(trace, Mode::Record(path)))
+}
+
+impl Tracer {
+    /// Replay `trace`, answering host calls from the trace.
+    #[must_use]
+    pub fn replay(trace: Trace) -> Self {
+        Self::new(trace, Mode::Replay)
+    }
+
+    fn new(trace: Trace, mode: Mode) -> Self {
+        Self {
+            tape: Arc::new(Mutex::new(Tape { trace, next: 0 })),
+            mode,
+        }
+    }
+
+    /// Record the request the guest is invoked with.
+    pub fn set_request(&self, request: &impl Serialize) {
+        match serde_json::to_value(request) {
+            Ok(request) => self.tape().trace.request = request,
+            Err(e) => tracing::error!("issue recording request: {e}"),
+        }
+    }
+
+    /// Run `future`, the guest invocation, tracing the host calls it makes.
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        TRACER.scope(self.clone(), future).await
+    }
+
+    /// Finish the invocation, writing the trace when recording, and logging
+    /// any error.
+    pub fn finish(self) {
+        let Tape { trace, next } = std::mem::take(&mut *self.tape());
+        match &self.mode {
+            Mode::Record(path) => match write(path, &trace) {
+                Ok(()) => tracing::info!("wrote host call trace {}", path.display()),
+                Err(e) => tracing::error!("issue writing host call trace: {e:#}"),
+            },
+            Mode::Replay => {
+                let remaining = trace.calls.len() - next;
+                if remaining > 0 {
+                    tracing::warn!("{remaining} recorded host calls were not replayed");
+                }
+            }
+        }
+    }
+
+    fn tape(&self) -> std::sync::MutexGuard<'_, Tape> {
+        self.tape.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Add a call to the trace, returning its position.
+    fn begin(&self, call: &str, args: Value) -> usize {
+        let mut tape = self.tape();
+        tape.trace.calls.push(HostCall {
+            call: call.to_string(),
+            args,
+            result: Err("did not complete".into()),
+        });
+        tape.trace.calls.len() - 1
+    }
+
+    // Set the result of the call at `index`.
+    fn complete(&self, index: usize, result: Result<Value, String>) {
+        if let Some(call) = self.tape().trace.calls.get_mut(index) {
+            call.result = result;
+        }
+    }
+
+    // Take the next recorded call, which must be a call to `call`.
+    fn next(&self, call: &str, args: &Value) -> Result<Value> {
+        let mut tape = self.tape();
+        let Some(recorded) = tape.trace.calls.get(tape.next).cloned() else {
+            tracing::error!("guest called `{call}` after every recorded host call was replayed");
+            return Err(anyhow!("trace has no host call left to replay `{call}`"));
+        };
+        if recorded.call != call {
+            tracing::error!("guest called `{call}` where `{}` was recorded", recorded.call);
+            return Err(anyhow!(
+                "guest diverged from trace: called `{call}` but `{}` was recorded",
+                recorded.call
+            ));
+        }
+        tape.next += 1;
+        drop(tape);
+
+        if &recorded.args != args {
+            tracing::warn!("`{call}` called with {args}, but {} was recorded", recorded.args);
+        }
+        recorded.result.map_err(|e| anyhow!(e))
+    }
+}
+
+/// Whether the host calls of the current guest invocation are being recorded
+/// or replayed.
+#[must_use]
+pub fn is_traced() -> bool {
+    TRACER.try_with(|_| ()).is_ok()
+}
+
+/// Record or replay a host call, `call`, made by the current guest
+/// invocation with `args`.
+///
+/// When recording, `run` makes the call and its result is added to the trace.
+/// When replaying, the recorded result is returned without calling `run`.
+/// Otherwise, `run` makes the call as normal.
+pub fn traced<A, T>(
+    call: &'static str, args: A, run: impl FnOnce() -> FutureResult<T>,
+) -> FutureResult<T>
+where
+    A: Serialize,
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    let Ok(tracer) = TRACER.try_with(Clone::clone) else {
+        return run();
+    };
+    let args = serde_json::to_value(args).unwrap_or_default();
+
+    match tracer.mode {
+        Mode::Record(_) => {
+            let index = tracer.begin(call, args);
+            let result = run();
+            Box::pin(async move {
+                let result = result.await;
+                let recorded = match &result {
+                    Ok(value) => serde_json::to_value(value).map_err(|e| e.to_string()),
+                    Err(e) => Err(format!("{e:#}")),
+                };
+                tracer.complete(index, recorded);
+                result
+            })
+        }
+        Mode::Replay => {
+            let recorded = tracer.next(call, &args);
+            Box::pin(async move {
+                serde_json::from_value(recorded?)
+                    .with_context(|| format!("invalid result for `{call}` in trace"))
+            })
+        }
+    }
+}
+
+/// Record or replay opening a backend resource, such as a bucket or a
+/// connection, with `args`.
+///
+/// Only whether `open` succeeded is traced. When replaying, `open` is not
+/// called and `None` is returned in place of the resource, as calls on the
+/// resource are themselves replayed from the trace.
+pub fn traced_open<A, H>(
+    call: &'static str, args: A, open: impl FnOnce() -> FutureResult<H>,
+) -> FutureResult<Option<H>>
+where
+    A: Serialize,
+    H: Send + 'static,
+{
+    let replaying = TRACER.try_with(|tracer| matches!(tracer.mode, Mode::Replay)).unwrap_or(false);
+    if replaying {
+        let result = traced::<_, ()>(call, args, || unreachable!("replayed calls are not made"));
+        return Box::pin(async move {
+            result.await?;
+            Ok(None)
+        });
+    }
+
+    let opened: Arc<Mutex<Option<H>>> = Arc::default();
+    let resource = Arc::clone(&opened);
+    let result = traced(call, args, move || {
+        let open = open();
+        Box::pin(async move {
+            *resource.lock().unwrap_or_else(PoisonError::into_inner) = Some(open.await?);
+            Ok(())
+        })
+    });
+    Box::pin(async move {
+        result.await?;
+        Ok(opened.lock().unwrap_or_else(PoisonError::into_inner).take())
+    })
+}
+
+// Write the trace as JSON.
+fn write(path: &Path, trace: &Trace) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    let json = serde_json::to_vec_pretty(trace).context("serializing trace")?;
+    fs::write(path, json).with_context(|| format!("writing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(key: &str) -> FutureResult<Option<String>> {
+        traced("keyvalue.get", key, || Box::pin(async { Ok(Some("recorded".to_string())) }))
+    }
+
+    #[tokio::test]
+    async fn record_replay() {
+        let path = std::env::temp_dir().join("qwasr-trace.json");
+        let recorder = Tracer::new(Trace::default(), Mode::Record(path));
+        let value = recorder.scope(async { get("key").await }).await.expect("should get");
+        assert_eq!(value.as_deref(), Some("recorded"));
+
+        let trace = recorder.tape().trace.clone();
+        assert_eq!(trace.calls.len(), 1);
+        assert_eq!(trace.calls[0].args, "key");
+
+        // replayed calls are answered from the trace, without being made
+        let replay = Tracer::replay(trace);
+        let value = replay
+            .scope(async {
+                traced("keyvalue.get", "key", || -> FutureResult<Option<String>> {
+                    panic!("replayed calls should not be made")
+                })
+                .await
+            })
+            .await
+            .expect("should replay");
+        assert_eq!(value.as_deref(), Some("recorded"));
+        replay.scope(async { get("key").await }).await.expect_err("trace should be exhausted");
+    }
+
+    #[test]
+    fn untraced_imports() {
+        let imports = [
+            "wasi:keyvalue/store@0.2.0-draft2",
+            "wasi:blobstore/blobstore@0.2.0-draft",
+            "wasi:cli/environment@0.2.6",
+            "wasi:vault/vault@0.1.0-draft",
+        ];
+        assert_eq!(
+            untraced(imports.into_iter()),
+            ["wasi:blobstore/blobstore@0.2.0-draft", "wasi:vault/vault@0.1.0-draft"]
+        );
+    }
+}
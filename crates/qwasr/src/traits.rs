@@ -6,10 +6,17 @@
 //! of a specific set of WASI interfaces.
 
 use std::fmt::Debug;
+use std::sync::Arc;
 
 use anyhow::Result;
 use futures::future::BoxFuture;
-use wasmtime::component::{InstancePre, Linker};
+use wasmtime::component::Linker;
+use wasmtime::{ResourceLimiterAsync, Store};
+use wasmtime_wasi::ResourceTable;
+
+use crate::{
+    Guest, GuestWasi, Guests, Instances, Policy, RuntimeOptions, Shutdown, StoreLimiter, Trace,
+};
 
 /// Result type for asynchronous operations.
 pub type FutureResult<T> = BoxFuture<'static, Result<T>>;
@@ -23,12 +30,65 @@ pub trait State: Clone + Send + Sync + 'static {
     #[must_use]
     fn store(&self) -> Self::StoreCtx;
 
-    /// Returns the pre-instantiated component.
-    fn instance_pre(&self) -> &InstancePre<Self::StoreCtx>;
+    /// Returns the guest components hosted by the runtime. Each component
+    /// may be replaced while the runtime is running.
+    fn guests(&self) -> &Guests<Self::StoreCtx>;
+
+    /// Returns the options the runtime was created with.
+    fn options(&self) -> &RuntimeOptions;
+
+    /// Returns the guest WASI context prepared when the runtime was created.
+    fn wasi(&self) -> &GuestWasi;
+
+    /// Returns the resource limiter held by the store context.
+    fn limiter(ctx: &mut Self::StoreCtx) -> &mut StoreLimiter;
+
+    /// Run the health check of each backend, returning the result for each
+    /// backend by name.
+    fn health(&self) -> impl Future<Output = Vec<(&'static str, Result<()>)>> + Send {
+        async { Vec::new() }
+    }
+
+    /// Returns the source of instances of `guest` used to invoke it outside
+    /// a server, for example from a test.
+    ///
+    /// A new source is created on each call unless the state keeps one per
+    /// guest, so warm instances are only filled once.
+    fn instances(&self, guest: &Guest<Self::StoreCtx>) -> Arc<Instances<Self>> {
+        Instances::new(self, guest)
+    }
+
+    /// Returns a new `Store` for a single guest invocation.
+    ///
+    /// The store yields to the async executor on each epoch tick and traps
+    /// the guest once the configured guest timeout has elapsed. When fuel
+    /// metering is enabled, the store starts with the configured fuel budget.
+    /// Memory, table, and instance growth is checked by the store context's
+    /// [`State::limiter`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a fuel budget is configured but the engine was not created
+    /// with fuel metering enabled.
+    #[must_use]
+    fn new_store(&self) -> Store<Self::StoreCtx> {
+        let options = self.options();
+        let mut store = Store::new(self.guests().engine(), self.store());
+        store.limiter_async(|ctx| Self::limiter(ctx) as &mut dyn ResourceLimiterAsync);
+        crate::epoch::set_deadline(&mut store, options.guest_timeout());
+        if let Some(fuel) = options.guest_fuel {
+            store.set_fuel(fuel).expect("fuel metering should be enabled");
+        }
+        store
+    }
 }
 
 /// Implemented by all WASI hosts in order to allow the runtime to link their
 /// dependencies.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be linked into a runtime with store context `{T}`",
+    note = "hosts outside the bundled `qwasr_wasi_*` crates implement their view trait for `T: qwasr::HostContext<{Self}>`, or name the macro implementing it with `{Self}: Backend => path::to::view_macro`"
+)]
 pub trait Host<T>: Debug + Sync + Send {
     /// Link the host's dependencies prior to component instantiation.
     ///
@@ -36,23 +96,62 @@ pub trait Host<T>: Debug + Sync + Send {
     ///
     /// Returns an linking error(s) from the service's generated bindings.
     fn add_to_linker(linker: &mut Linker<T>) -> Result<()>;
+
+    /// The environment variables read by the host itself, for example by its
+    /// server, rather than by its backend's connection options.
+    ///
+    /// Configuration file keys are only accepted when they set a variable read
+    /// by one of the runtime's hosts or backends.
+    #[must_use]
+    fn env_vars() -> Vec<String> {
+        Vec::new()
+    }
 }
 
 /// Implemented by WASI hosts that are servers in order to allow the runtime to
 /// start them.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be started by the runtime",
+    note = "hosts that are not servers implement `Server` with its default `run`: `impl<S: qwasr::State> qwasr::Server<S> for {Self} {{}}`"
+)]
 pub trait Server<S: State>: Debug + Sync + Send {
     /// Start the service.
     ///
     /// This is typically implemented by services that instantiate (or run)
-    /// wasm components.
+    /// wasm components. Services should stop accepting new work and return
+    /// once `shutdown` is signalled, spawning guest invocations with
+    /// [`Shutdown::spawn`] so they are drained before the runtime exits. A
+    /// service returning before `shutdown` is signalled is restarted as
+    /// failed.
+    ///
+    /// Hosts that are not servers wait for `shutdown`.
     #[allow(unused_variables)]
-    fn run(&self, state: &S) -> impl Future<Output = Result<()>> {
-        async { Ok(()) }
+    fn run(&self, state: &S, shutdown: Shutdown) -> impl Future<Output = Result<()>> {
+        async move {
+            shutdown.signalled().await;
+            Ok(())
+        }
+    }
+
+    /// Replay the guest invocation recorded in `trace`, answering the guest's
+    /// host calls from the trace.
+    ///
+    /// Servers replay the invocations they recorded, returning `None` for
+    /// traces recorded by other servers.
+    #[allow(unused_variables)]
+    fn replay(
+        &self, state: &S, trace: &Trace, shutdown: Shutdown,
+    ) -> impl Future<Output = Option<Result<()>>> {
+        async { None }
     }
 }
 
 /// Implemented by backend resources to allow the backend to be connected to a
 /// WASI component.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a backend",
+    note = "backends implement `qwasr::Backend` so the runtime can connect to them"
+)]
 pub trait Backend: Sized + Sync + Send {
     /// The options used to connect to the backend.
     type ConnectOptions: FromEnv;
@@ -65,6 +164,41 @@ pub trait Backend: Sized + Sync + Send {
 
     /// Connect to the resource with the specified options.
     fn connect_with(options: Self::ConnectOptions) -> impl Future<Output = Result<Self>>;
+
+    /// Check the backend is able to serve requests.
+    ///
+    /// Used by the admin server's readiness endpoint. By default, backends are
+    /// always reported as healthy.
+    fn health_check(&self) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+}
+
+/// Implemented by the store context of a `runtime!` build for each host it
+/// links, giving the host access to its backend.
+///
+/// Hosts outside the bundled `qwasr_wasi_*` crates can implement their view
+/// trait for any `T: HostContext<Host>` rather than providing a view macro:
+///
+/// ```rust,ignore
+/// impl<T> InventoryView for T
+/// where
+///     T: HostContext<Inventory> + Send,
+///     T::Context: InventoryCtx,
+/// {
+///     fn inventory(&mut self) -> InventoryCtxView<'_> {
+///         let (ctx, table, policy) = self.host_context();
+///         InventoryCtxView { ctx, table, policy }
+///     }
+/// }
+/// ```
+pub trait HostContext<H> {
+    /// The backend providing the host.
+    type Context;
+
+    /// Returns the host's backend, together with the resource table and
+    /// capability policy.
+    fn host_context(&mut self) -> (&mut Self::Context, &mut ResourceTable, &Policy);
 }
 
 /// Trait for creating connection options from environment variables.
@@ -75,6 +209,25 @@ pub trait FromEnv: Sized {
     ///
     /// Returns an error if required environment variables are missing or invalid.
     fn from_env() -> Result<Self>;
+
+    /// The environment variables read by [`FromEnv::from_env`].
+    ///
+    /// Options derived with `fromenv` return the variables listed by their
+    /// `requirements`, using [`requirement_vars`].
+    #[must_use]
+    fn env_vars() -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The names of the environment variables listed in `requirements`, as
+/// returned by the `requirements` function of options derived with `fromenv`.
+#[must_use]
+pub fn requirement_vars(requirements: &str) -> Vec<String> {
+    requirements
+        .lines()
+        .filter_map(|line| line.split_once('=').map(|(name, _)| name.to_string()))
+        .collect()
 }
 
 // /// Implemented by `StoreCtx` to provide access to a specific host's context.
@@ -0,0 +1,280 @@
+//! # WASI Context
+//!
+//! Options controlling the environment variables, arguments, preopened
+//! directories, and stdin made available to each guest through its
+//! `WasiCtx`.
+//!
+//! The options are prepared once, when the runtime is created, as a
+//! [`GuestWasi`] that builds the `WasiCtx` for each guest `Store` without
+//! reading the host environment or opening directories again.
+
+#![allow(missing_docs)]
+
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use fromenv::FromEnv;
+use wasmtime_wasi::filesystem::WasiFilesystemCtx;
+use wasmtime_wasi::{DirPerms, FilePerms, WasiCtx, WasiCtxBuilder};
+
+/// Options used to build each guest's `WasiCtx`.
+///
+/// Options are loaded from environment variables.
+#[derive(Debug, Clone, FromEnv)]
+pub struct WasiOptions {
+    /// Comma-separated names of the host environment variables passed to the
+    /// guest. Names ending in `*` pass every variable with that prefix. When
+    /// unset, no host environment variables are passed.
+    #[env(from = "GUEST_ENV")]
+    pub env: Option<String>,
+
+    /// Comma-separated arguments passed to the guest. A comma that is part
+    /// of an argument is escaped as `\,`, and a backslash before a comma as
+    /// `\\`.
+    #[env(from = "GUEST_ARGS")]
+    pub args: Option<String>,
+
+    /// Comma-separated directories preopened for the guest, each in the form
+    /// `host_path[:guest_path][:ro|:rw]`. Directories are read-only unless
+    /// `rw` is specified.
+    #[env(from = "GUEST_DIRS")]
+    pub dirs: Option<String>,
+
+    /// Pass the host's stdin to the guest.
+    #[env(from = "GUEST_STDIN", default = "true")]
+    pub stdin: bool,
+}
+
+impl crate::FromEnv for WasiOptions {
+    fn from_env() -> Result<Self> {
+        Self::from_env().finalize().context("issue loading WASI options")
+    }
+}
+
+/// A host directory preopened for the guest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preopen {
+    /// The directory on the host.
+    pub host: PathBuf,
+
+    /// The path the guest sees the directory at.
+    pub guest: String,
+
+    /// Whether the guest may create, modify, and delete files.
+    pub writable: bool,
+}
+
+impl WasiOptions {
+    /// The directories preopened for the guest.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory is malformed or does not exist.
+    pub fn preopens(&self) -> Result<Vec<Preopen>> {
+        let Some(dirs) = &self.dirs else {
+            return Ok(Vec::new());
+        };
+
+        let mut preopens = Vec::new();
+        for dir in dirs.split(',').map(str::trim).filter(|dir| !dir.is_empty()) {
+            let mut parts: Vec<&str> = dir.split(':').collect();
+            let writable = match parts.last() {
+                Some(&"rw") => true,
+                Some(&"ro") => false,
+                _ => {
+                    parts.push("ro");
+                    false
+                }
+            };
+            let (host, guest) = match parts.as_slice() {
+                [host, _] => (*host, *host),
+                [host, guest, _] => (*host, *guest),
+                _ => bail!("invalid guest directory `{dir}`: expected `host[:guest][:ro|:rw]`"),
+            };
+            if host.is_empty() || guest.is_empty() {
+                bail!("invalid guest directory `{dir}`: paths cannot be empty");
+            }
+
+            let host = PathBuf::from(host);
+            if !host.is_dir() {
+                bail!("invalid guest directory `{dir}`: {} is not a directory", host.display());
+            }
+            preopens.push(Preopen {
+                host,
+                guest: guest.to_string(),
+                writable,
+            });
+        }
+
+        Ok(preopens)
+    }
+
+    /// Whether the host environment variable `name` is passed to the guest.
+    #[must_use]
+    pub fn allows_env(&self, name: &str) -> bool {
+        let Some(allowed) = &self.env else {
+            return false;
+        };
+        allowed.split(',').map(str::trim).filter(|entry| !entry.is_empty()).any(|entry| {
+            entry.strip_suffix('*').map_or(entry == name, |prefix| name.starts_with(prefix))
+        })
+    }
+
+    // The arguments passed to the guest, skipping empty entries. `\,` is a
+    // comma within an argument and `\\` a backslash; other backslashes are
+    // kept as they are.
+    fn args(&self) -> Vec<String> {
+        let Some(args) = self.args.as_deref() else {
+            return Vec::new();
+        };
+
+        let mut split = Vec::new();
+        let mut arg = String::new();
+        let mut chars = args.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(escaped @ (',' | '\\')) => arg.push(escaped),
+                    Some(next) => arg.extend(['\\', next]),
+                    None => arg.push('\\'),
+                },
+                ',' => split.push(std::mem::take(&mut arg)),
+                c => arg.push(c),
+            }
+        }
+        split.push(arg);
+
+        split.iter().map(|arg| arg.trim()).filter(|arg| !arg.is_empty()).map(Into::into).collect()
+    }
+}
+
+/// The guest WASI context prepared from [`WasiOptions`]: the allowed host
+/// environment variables, the guest's arguments, and its opened preopened
+/// directories.
+///
+/// Prepared once when the runtime is created, then used to build the
+/// `WasiCtx` for every guest `Store`.
+#[derive(Clone)]
+pub struct GuestWasi {
+    env: Vec<(String, String)>,
+    args: Vec<String>,
+    filesystem: WasiFilesystemCtx,
+    stdin: bool,
+}
+
+impl GuestWasi {
+    /// Prepare the guest WASI context from `options`, reading the allowed
+    /// host environment variables and opening the preopened directories.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory is malformed, does not exist, or
+    /// cannot be opened.
+    pub fn new(options: &WasiOptions) -> Result<Self> {
+        let mut builder = WasiCtxBuilder::new();
+        for preopen in options.preopens()? {
+            let (dir_perms, file_perms) = if preopen.writable {
+                (DirPerms::all(), FilePerms::all())
+            } else {
+                (DirPerms::READ, FilePerms::READ)
+            };
+            builder
+                .preopened_dir(&preopen.host, &preopen.guest, dir_perms, file_perms)
+                .with_context(|| format!("preopening {}", preopen.host.display()))?;
+        }
+
+        Ok(Self {
+            env: env::vars().filter(|(key, _)| options.allows_env(key)).collect(),
+            args: options.args(),
+            filesystem: builder.build().filesystem().clone(),
+            stdin: options.stdin,
+        })
+    }
+
+    /// Build a `WasiCtx` for a guest.
+    #[must_use]
+    pub fn wasi_ctx(&self) -> WasiCtx {
+        self.build(&self.args)
+    }
+
+    /// Build a `WasiCtx` for a command guest run as `program`, passing `args`
+    /// in place of `GUEST_ARGS`.
+    #[must_use]
+    pub fn command_ctx(&self, program: &str, args: &[String]) -> WasiCtx {
+        let args: Vec<String> = std::iter::once(program.to_string()).chain(args.to_vec()).collect();
+        self.build(&args)
+    }
+
+    // Build a `WasiCtx` passing `args` to the guest.
+    fn build(&self, args: &[String]) -> WasiCtx {
+        let mut builder = WasiCtxBuilder::new();
+        builder.stdout(tokio::io::stdout()).stderr(tokio::io::stderr());
+        if self.stdin {
+            builder.inherit_stdin();
+        }
+        builder.envs(&self.env).args(args);
+
+        let mut ctx = builder.build();
+        *ctx.filesystem() = self.filesystem.clone();
+        ctx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(env: Option<&str>, dirs: Option<&str>) -> WasiOptions {
+        WasiOptions {
+            env: env.map(ToString::to_string),
+            args: None,
+            dirs: dirs.map(ToString::to_string),
+            stdin: false,
+        }
+    }
+
+    #[test]
+    fn env_allow_list() {
+        assert!(!options(None, None).allows_env("REDIS_URL"));
+
+        let options = options(Some("RUST_LOG, APP_*"), None);
+        assert!(options.allows_env("RUST_LOG"));
+        assert!(options.allows_env("APP_NAME"));
+        assert!(!options.allows_env("RUST_LOG_STYLE"));
+        assert!(!options.allows_env("REDIS_URL"));
+    }
+
+    #[test]
+    fn preopens() {
+        let tmp = env::temp_dir();
+        let tmp_str = tmp.to_str().expect("should be utf-8");
+        let dirs = format!("{tmp_str}, {tmp_str}:/data:rw, {tmp_str}:/config");
+        let preopens = options(None, Some(&dirs)).preopens().expect("should parse");
+
+        assert_eq!(preopens.len(), 3);
+        assert_eq!(preopens[0].guest, tmp_str);
+        assert!(!preopens[0].writable);
+        assert_eq!(preopens[1].guest, "/data");
+        assert!(preopens[1].writable);
+        assert_eq!(preopens[2].guest, "/config");
+        assert!(!preopens[2].writable);
+
+        options(None, Some("/does/not/exist")).preopens().expect_err("should reject missing dir");
+        options(None, Some(&format!("{tmp_str}:a:b:rw")))
+            .preopens()
+            .expect_err("should reject malformed dir");
+    }
+
+    #[test]
+    fn args() {
+        let mut options = options(None, None);
+        assert!(options.args().is_empty());
+        options.args = Some(String::new());
+        assert!(options.args().is_empty());
+        options.args = Some("--verbose, , run".into());
+        assert_eq!(options.args(), ["--verbose", "run"]);
+        options.args = Some(r"--tags=a\,b, C:\data\\, --end\".into());
+        assert_eq!(options.args(), ["--tags=a,b", r"C:\data\", r"--end\"]);
+    }
+}
@@ -3,105 +3,147 @@
 //! Expands the parsed runtime configuration into a complete runtime implementation.
 
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
 use syn::{Ident, Path};
 
-use crate::runtime::Config;
+use crate::runtime::{Backends, Config, Host};
+
+// Hosts provided by the bundled `qwasr_wasi_*` crates, whose view macros are
+// found by naming convention.
+const BUNDLED_HOSTS: &[&str] = &[
+    "WasiBlobstore",
+    "WasiConfig",
+    "WasiHttp",
+    "WasiIdentity",
+    "WasiKeyValue",
+    "WasiMessaging",
+    "WasiOtel",
+    "WasiSql",
+    "WasiVault",
+    "WasiWebSockets",
+];
+
+// Names of the `StoreCtx` fields holding runtime state, which cannot be used
+// for hosts.
+const RESERVED_FIELDS: &[&str] = &["table", "wasi", "limits", "policy"];
 
 // Generate the runtime from the configuration.
 pub fn expand(config: &Config) -> syn::Result<TokenStream> {
     let Expanded {
         context_fields,
+        backend_fields,
+        backend_types,
+        host_names,
+        sections,
         store_ctx_fields,
         store_ctx_values,
         host_trait_impls,
-        server_trait_impls,
+        server_arms,
+        replay_arms,
         wasi_view_impls,
+        host_assertions,
         main_fn,
     } = Expanded::try_from(config)?;
+    let state_impl = state_impl(&backend_fields, &store_ctx_values);
+    let services_impl = services_impl(&backend_fields, &backend_types, &server_arms, &replay_arms);
+    let one_shot_fns = one_shot_fns();
+    let assert_hosts = assert_hosts(&host_assertions);
+    let options_fn = options_fn(&host_trait_impls, &backend_types);
 
     Ok(quote! {
         mod runtime {
+            // `Backend::connect` and `Server::run` futures are not required to be `Send`
+            #![allow(clippy::future_not_send)]
+
             use std::path::PathBuf;
+            use std::process::ExitCode;
+            use std::sync::{Arc, OnceLock};
 
             use anyhow::Result;
             use qwasr::anyhow::Context as _;
-            use qwasr::futures::future::{try_join_all, BoxFuture};
+            use qwasr::futures::future::{join_all, BoxFuture, LocalBoxFuture};
             use qwasr::tokio;
-            use qwasr::wasmtime::component::{HasData,InstancePre};
-            use qwasr::wasmtime_wasi::{ResourceTable, WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView};
-            use qwasr::{Backend, Compiled, Server, State};
+            use qwasr::wasmtime::component::HasData;
+            use qwasr::wasmtime_wasi::{ResourceTable, WasiCtx, WasiCtxView, WasiView};
+            use qwasr::{Backend, Compiled, GuestWasi, Guests, Policy, RuntimeOptions, Server, Services, Shutdown, State, StoreLimiter};
 
             use super::*;
 
-            /// Run the specified wasm guest using the configured runtime.
-            pub async fn run(wasm: PathBuf) -> Result<()> {
-                let mut compiled = qwasr::create(&wasm)
-                    .with_context(|| format!("compiling {}", wasm.display()))?;
-                let run_state = Context::new(&mut compiled)
-                    .await
-                    .context("preparing runtime state")?;
-                run_state.start().await.context("starting runtime services")
+            /// Run the specified wasm guests using the configured runtime.
+            pub async fn run(wasm: Vec<PathBuf>) -> Result<()> {
+                let mut compiled = qwasr::create(&wasm).context("creating runtime")?;
+                let run_state = Context::new(&mut compiled).context("preparing runtime state")?;
+
+                // reload guests on SIGHUP or when their wasm file changes
+                let reload_watch = compiled.options().reload_watch();
+                for reloader in compiled.reloaders(&run_state.guests) {
+                    reloader.start(reload_watch);
+                }
+
+                qwasr::start(&run_state).await.context("starting runtime services")
+            }
+
+            #one_shot_fns
+
+            /// Inspect the specified wasm guest against the hosts linked into this runtime.
+            pub fn inspect(wasm: PathBuf, json: bool) -> Result<()> {
+                let hosts: &[qwasr::LinkHost<StoreCtx>] = &[
+                    #((#host_names, <#host_trait_impls as qwasr::Host<StoreCtx>>::add_to_linker),)*
+                ];
+                qwasr::inspect(&wasm, hosts)?.print(json)
             }
 
             /// Initiator state holding pre-instantiated components and backend connections.
             #[derive(Clone)]
             struct Context {
-                instance_pre: InstancePre<StoreCtx>,
+                guests: Guests<StoreCtx>,
+                options: RuntimeOptions,
+                policy: Arc<Policy>,
+                wasi: Arc<GuestWasi>,
+                backends: Arc<OnceLock<Backends>>,
+            }
+
+            /// Backend connections, set once every backend has connected.
+            #[derive(Clone)]
+            struct Backends {
                 #(pub #context_fields,)*
             }
 
             impl Context {
-                /// Creates a new runtime state by linking WASI interfaces and connecting to backends.
-                async fn new(compiled: &mut Compiled<StoreCtx>) -> Result<Self> {
+                /// Creates a new runtime state by linking WASI interfaces.
+                fn new(compiled: &mut Compiled<StoreCtx>) -> Result<Self> {
                     // link enabled WASI components
                     #(compiled.link(#host_trait_impls)?;)*
 
                     Ok(Self {
-                        instance_pre: compiled.pre_instantiate()?,
-                        #(#context_fields::connect().await?,)*
+                        guests: compiled.pre_instantiate()?,
+                        options: compiled.options().clone(),
+                        policy: compiled.policy(),
+                        wasi: compiled.wasi(),
+                        backends: Arc::new(OnceLock::new()),
                     })
                 }
-
-                /// Start servers.
-                ///
-                /// N.B. for simplicity, all hosts are "servers" with a default implementation that does nothing.
-                async fn start(&self) -> Result<()> {
-                    let futures: Vec<BoxFuture<'_, Result<()>>> =
-                        vec![#(Box::pin(#server_trait_impls.run(self)),)*];
-                    try_join_all(futures).await?;
-                    Ok(())
-                }
             }
 
-            impl State for Context {
-                type StoreCtx = StoreCtx;
+            /// WASI hosts linked to the guest component.
+            const HOSTS: &[&str] = &[#(#host_names),*];
 
-                fn instance_pre(&self) -> &InstancePre<Self::StoreCtx> {
-                    &self.instance_pre
-                }
+            /// Configuration file sections: one per host and backend.
+            pub const SECTIONS: &[&str] = &[#(#sections),*];
 
-                fn store(&self) -> Self::StoreCtx {
-                    let wasi_ctx = WasiCtxBuilder::new()
-                        // .inherit_args()
-                        .inherit_env()
-                        .inherit_stdin()
-                        .stdout(tokio::io::stdout())
-                        .stderr(tokio::io::stderr())
-                        .build();
-
-                    StoreCtx {
-                        table: ResourceTable::new(),
-                        wasi: wasi_ctx,
-                        #(#store_ctx_values,)*
-                    }
-                }
-            }
+            #options_fn
+
+            #state_impl
+
+            #services_impl
 
             /// Per-guest instance data shared between the runtime and the guest.
             pub struct StoreCtx {
                 pub table: ResourceTable,
                 pub wasi: WasiCtx,
+                pub limits: StoreLimiter,
+                pub policy: Arc<Policy>,
                 #(pub #store_ctx_fields,)*
             }
 
@@ -117,6 +159,8 @@ pub fn expand(config: &Config) -> syn::Result<TokenStream> {
 
             // WASI view implementations for enabled hosts.
             #(#wasi_view_impls)*
+
+            #assert_hosts
         }
 
         // Main function (optional)
@@ -124,13 +168,176 @@ pub fn expand(config: &Config) -> syn::Result<TokenStream> {
     })
 }
 
+// Generate checks that each host can be linked and served, and each backend
+// connected.
+fn assert_hosts(host_assertions: &[TokenStream]) -> TokenStream {
+    quote! {
+        // Check each host can be linked and served, and each backend connected,
+        // reporting errors against the host in the macro input.
+        #[allow(dead_code)]
+        fn assert_hosts() {
+            fn host<H: qwasr::Host<StoreCtx> + Server<Context>>() {}
+            fn backend<B: Backend + Clone>() {}
+            fn named<H: qwasr::NamedHost>() {}
+            #(#host_assertions)*
+        }
+    }
+}
+
+// Generate the function listing the environment variables configuration file
+// sections may set.
+fn options_fn(host_trait_impls: &[Path], backend_types: &[Path]) -> TokenStream {
+    quote! {
+        /// The environment variables read by the hosts and backends, which
+        /// keys in their configuration file sections may set.
+        pub fn options() -> Vec<String> {
+            let mut options = Vec::new();
+            #(options.extend(<#host_trait_impls as qwasr::Host<StoreCtx>>::env_vars());)*
+            #(options.extend(<<#backend_types as Backend>::ConnectOptions as qwasr::FromEnv>::env_vars());)*
+            options
+        }
+    }
+}
+
+// Generate the `State` implementation for the runtime `Context`.
+fn state_impl(backend_fields: &[Ident], store_ctx_values: &[TokenStream]) -> TokenStream {
+    quote! {
+        impl State for Context {
+            type StoreCtx = StoreCtx;
+
+            fn guests(&self) -> &Guests<Self::StoreCtx> {
+                &self.guests
+            }
+
+            fn options(&self) -> &RuntimeOptions {
+                &self.options
+            }
+
+            fn wasi(&self) -> &GuestWasi {
+                &self.wasi
+            }
+
+            fn limiter(ctx: &mut Self::StoreCtx) -> &mut StoreLimiter {
+                &mut ctx.limits
+            }
+
+            async fn health(&self) -> Vec<(&'static str, Result<()>)> {
+                let Some(backends) = self.backends.get() else {
+                    return vec![
+                        #((stringify!(#backend_fields), Err(qwasr::anyhow::anyhow!("not connected"))),)*
+                    ];
+                };
+                let checks: Vec<BoxFuture<'_, (&'static str, Result<()>)>> = vec![
+                    #(Box::pin(async {
+                        (stringify!(#backend_fields), backends.#backend_fields.health_check().await)
+                    }),)*
+                ];
+                join_all(checks).await
+            }
+
+            fn store(&self) -> Self::StoreCtx {
+                #[allow(unused_variables)]
+                let backends = self
+                    .backends
+                    .get()
+                    .expect("backends should be connected before guests are instantiated");
+                StoreCtx {
+                    table: ResourceTable::new(),
+                    wasi: self.wasi.wasi_ctx(),
+                    limits: StoreLimiter::new(self.options.limits.clone()),
+                    policy: Arc::clone(&self.policy),
+                    #(#store_ctx_values,)*
+                }
+            }
+    }
+    }
+}
+
+// Generate the `Services` implementation for the runtime `Context`, connecting
+// backends in parallel and running or replaying with each host's server.
+fn services_impl(
+    backend_fields: &[Ident], backend_types: &[Path], server_arms: &[TokenStream],
+    replay_arms: &[TokenStream],
+) -> TokenStream {
+    let connect = if backend_fields.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            let (#(#backend_fields,)*) = tokio::try_join!(#(
+                qwasr::connect_backend::<#backend_types>(stringify!(#backend_fields), options, shutdown),
+            )*)?;
+        }
+    };
+
+    quote! {
+        /// N.B. for simplicity, all hosts are "servers" with a default implementation that does nothing.
+        impl Services for Context {
+            fn hosts(&self) -> &[&'static str] {
+                HOSTS
+            }
+
+            async fn connect(&self, shutdown: &Shutdown) -> Result<()> {
+                let options = &self.options.backends;
+                #connect
+                let _ = self.backends.set(Backends { #(#backend_fields,)* });
+                Ok(())
+            }
+
+            fn serve(&self, host: &'static str, shutdown: Shutdown) -> LocalBoxFuture<'_, Result<()>> {
+                match host {
+                    #(#server_arms)*
+                    _ => Box::pin(async move { Err(qwasr::anyhow::anyhow!("host `{host}` is not enabled")) }),
+                }
+            }
+
+            fn replay<'a>(
+                &'a self, host: &'static str, trace: &'a qwasr::Trace, shutdown: Shutdown,
+            ) -> LocalBoxFuture<'a, Option<Result<()>>> {
+                match host {
+                    #(#replay_arms)*
+                    _ => Box::pin(async { None }),
+                }
+            }
+        }
+    }
+}
+
+// Generate the functions running a guest once rather than serving: `exec` and
+// `replay`.
+fn one_shot_fns() -> TokenStream {
+    quote! {
+        /// Run the specified command guest once, passing it `args`, and return
+        /// its exit code.
+        pub async fn exec(wasm: PathBuf, args: Vec<String>) -> Result<ExitCode> {
+            let mut compiled = qwasr::create(&[wasm]).context("creating runtime")?;
+            let run_state = Context::new(&mut compiled).context("preparing runtime state")?;
+            qwasr::run_command(&run_state, &args).await
+        }
+
+        /// Replay the guest invocation recorded in `trace` against the specified
+        /// wasm guest.
+        pub async fn replay(wasm: PathBuf, trace: PathBuf) -> Result<()> {
+            let trace = qwasr::Trace::load(&trace)?;
+            let mut compiled = qwasr::create(&[wasm]).context("creating runtime")?;
+            let run_state = Context::new(&mut compiled).context("preparing runtime state")?;
+            qwasr::replay(&run_state, &trace).await
+        }
+    }
+}
+
 struct Expanded {
     context_fields: Vec<TokenStream>,
+    backend_fields: Vec<Ident>,
+    backend_types: Vec<Path>,
+    host_names: Vec<String>,
+    sections: Vec<String>,
     store_ctx_fields: Vec<TokenStream>,
     store_ctx_values: Vec<TokenStream>,
     host_trait_impls: Vec<Path>,
-    server_trait_impls: Vec<TokenStream>,
+    server_arms: Vec<TokenStream>,
+    replay_arms: Vec<TokenStream>,
     wasi_view_impls: Vec<TokenStream>,
+    host_assertions: Vec<TokenStream>,
     main_fn: TokenStream,
 }
 
@@ -139,99 +346,254 @@ impl TryFrom<&Config> for Expanded {
 
     fn try_from(input: &Config) -> Result<Self, Self::Error> {
         // `Context` struct
+        let fields = BackendFields::new(&input.backends);
         let mut context_fields = Vec::new();
-        let mut seen_backends = Vec::new();
-
-        for backend in &input.backends {
-            // deduplicate backends based on their string representation
-            let backend_str = quote! {#backend}.to_string();
-            if seen_backends.contains(&backend_str) {
-                continue;
-            }
-            seen_backends.push(backend_str);
+        let mut backend_fields = Vec::new();
+        let mut backend_types = Vec::new();
 
-            let field = field_ident(backend);
+        for (backend, field) in input.backends.iter().zip(&fields.idents) {
             context_fields.push(quote! {#field: #backend});
+            backend_fields.push(field.clone());
+            backend_types.push(backend.clone());
         }
 
         let mut store_ctx_fields = Vec::new();
         let mut store_ctx_values = Vec::new();
+        let mut host_names = Vec::new();
+        let mut sections = Vec::new();
         let mut host_trait_impls = Vec::new();
-        let mut server_trait_impls = Vec::new();
+        let mut server_arms = Vec::new();
+        let mut replay_arms = Vec::new();
         let mut wasi_view_impls = Vec::new();
+        let mut host_assertions = Vec::new();
 
         for host in &input.hosts {
             let host_type = &host.type_;
+            let host_name = host.name();
             let host_ident = wasi_ident(host_type);
-            let backend_type = &host.backend;
-            let backend_ident = field_ident(backend_type);
+            if RESERVED_FIELDS.contains(&host_ident.to_string().as_str()) {
+                return Err(syn::Error::new_spanned(
+                    host_type,
+                    format!("host `{host_name}` conflicts with the runtime's `{host_ident}` field"),
+                ));
+            }
 
             host_trait_impls.push(host_type.clone());
-            store_ctx_fields.push(quote! {#host_ident: #backend_type});
-            store_ctx_values.push(quote! {#host_ident: self.#backend_ident.clone()});
+            host_names.push(host_name.clone());
+            sections.push(section(host_type));
+
+            let (context_type, context_value) =
+                store_ctx_backend(host_type, &host.backend, &fields);
+            store_ctx_values.push(quote! {#host_ident: #context_value});
+            store_ctx_fields.push(quote! {#host_ident: #context_type});
 
             // servers
-            server_trait_impls.push(quote! {#host_type});
+            server_arms.push(quote! {
+                #host_name => Box::pin(async move { #host_type.run(self, shutdown).await }),
+            });
+            replay_arms.push(quote! {
+                #host_name => Box::pin(async move { #host_type.replay(self, trace, shutdown).await }),
+            });
 
-            // WASI view impls
-            // HACK: derive module name from WASI type
-            let module = wasi_ident(host_type);
+            // WASI view impls: an explicit view macro, the bundled crate's view
+            // macro, or the host's own impl for `qwasr::HostContext`
             wasi_view_impls.push(quote! {
-                #module::qwasr_wasi_view!(StoreCtx, #host_ident);
-            });
-        }
+                impl qwasr::HostContext<#host_type> for StoreCtx {
+                    type Context = #context_type;
 
-        // main function (optional)
-        let main_fn = if input.gen_main {
-            quote! {
-                use qwasr::tokio;
-
-                #[tokio::main]
-                async fn main() -> anyhow::Result<()> {
-                    use qwasr::Parser;
-                    match qwasr::Cli::parse().command {
-                        qwasr::Command::Run { wasm } => runtime::run(wasm).await,
-                        _ => unreachable!(),
+                    fn host_context(&mut self) -> (&mut Self::Context, &mut ResourceTable, &Policy) {
+                        (&mut self.#host_ident, &mut self.table, &self.policy)
                     }
                 }
+            });
+            if let Some(view) = &host.view {
+                wasi_view_impls.push(quote_spanned! {view.span()=>
+                    #view!(StoreCtx, #host_ident);
+                });
+            } else if BUNDLED_HOSTS.contains(&host_name.as_str()) {
+                let module = wasi_ident(host_type);
+                wasi_view_impls.push(quote! {
+                    #module::qwasr_wasi_view!(StoreCtx, #host_ident);
+                });
             }
-        } else {
-            quote! {}
-        };
+
+            host_assertions.extend(assertions(host));
+        }
+        sections.extend(backend_fields.iter().map(ToString::to_string));
+
+        let main_fn = main_fn(input.gen_main);
 
         Ok(Self {
             context_fields,
+            backend_fields,
+            backend_types,
+            host_names,
+            sections,
             store_ctx_fields,
             store_ctx_values,
             host_trait_impls,
-            server_trait_impls,
+            server_arms,
+            replay_arms,
             wasi_view_impls,
+            host_assertions,
             main_fn,
         })
     }
 }
 
-/// Generates a field name for a backend type.
-fn field_ident(path: &Path) -> Ident {
-    let Some(ident) = path.segments.last() else {
-        return format_ident!("field");
-    };
-    let ident_str = quote! {#ident}.to_string();
+// Generate the optional `main` function, dispatching CLI commands to the
+// generated runtime.
+fn main_fn(gen_main: bool) -> TokenStream {
+    if !gen_main {
+        return quote! {};
+    }
+    quote! {
+        fn main() -> anyhow::Result<std::process::ExitCode> {
+            use std::process::ExitCode;
 
-    // convert the type string to snake_case
-    let mut field_str = String::new();
-    for char in ident_str.chars() {
+            use qwasr::Parser;
+            use qwasr::anyhow::Context as _;
+
+            // load configuration into the environment before the async
+            // runtime starts its worker threads
+            let command = qwasr::Cli::parse().command;
+            command.configure(runtime::SECTIONS, &runtime::options())?;
+
+            let rt = qwasr::tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .context("building async runtime")?;
+            rt.block_on(async move {
+                match command {
+                    qwasr::Command::Run { wasm, .. } => {
+                        runtime::run(wasm).await.map(|()| ExitCode::SUCCESS)
+                    }
+                    qwasr::Command::Exec { wasm, args, .. } => runtime::exec(wasm, args).await,
+                    qwasr::Command::Replay { wasm, trace, .. } => {
+                        runtime::replay(wasm, trace).await.map(|()| ExitCode::SUCCESS)
+                    }
+                    qwasr::Command::Inspect { wasm, json } => {
+                        runtime::inspect(wasm, json).map(|()| ExitCode::SUCCESS)
+                    }
+                    _ => unreachable!(),
+                }
+            })
+        }
+    }
+}
+
+// Generate the type of the `StoreCtx` field providing a host, and its value:
+// a single backend, or the backends chosen by the name opened by the guest.
+fn store_ctx_backend(
+    host_type: &Path, backend: &Backends, fields: &BackendFields,
+) -> (TokenStream, TokenStream) {
+    match backend {
+        Backends::Single(backend_type) => {
+            let backend_ident = fields.get(backend_type);
+            (quote! {#backend_type}, quote! {backends.#backend_ident.clone()})
+        }
+        Backends::Named(named) => {
+            let ctx = quote_spanned! {host_type.span()=>
+                <#host_type as qwasr::NamedHost>::Ctx
+            };
+            let bound = named.iter().map(|(pattern, backend_type)| {
+                let backend_ident = fields.get(backend_type);
+                quote_spanned! {backend_type.span()=>
+                    (#pattern.to_string(), Arc::new(backends.#backend_ident.clone()) as Arc<#ctx>)
+                }
+            });
+            (
+                quote! {qwasr::NamedBackends<#ctx>},
+                quote! {qwasr::NamedBackends::new(vec![#(#bound),*])},
+            )
+        }
+    }
+}
+
+// Generate checks the host can be linked and served, and its backends
+// connected, spanned so errors are reported against the macro input.
+fn assertions(host: &Host) -> Vec<TokenStream> {
+    let host_type = &host.type_;
+    let mut assertions = vec![quote_spanned! {host_type.span()=>
+        host::<#host_type>();
+    }];
+    if matches!(host.backend, Backends::Named(_)) {
+        assertions.push(quote_spanned! {host_type.span()=>
+            named::<#host_type>();
+        });
+    }
+    for backend_type in host.backend.paths() {
+        assertions.push(quote_spanned! {backend_type.span()=>
+            backend::<#backend_type>();
+        });
+    }
+    assertions
+}
+
+// The `Backends` field holding each backend, named after the backend's type
+// in snake case. Backends sharing a type name, such as `a::Db` and `b::Db`,
+// are named after their full path instead.
+struct BackendFields {
+    paths: Vec<String>,
+    idents: Vec<Ident>,
+}
+
+impl BackendFields {
+    fn new(backends: &[Path]) -> Self {
+        let names: Vec<String> = backends
+            .iter()
+            .map(|path| path.segments.last().map(|s| snake_case(&s.ident)).unwrap_or_default())
+            .collect();
+
+        let mut idents: Vec<Ident> = Vec::new();
+        for (i, path) in backends.iter().enumerate() {
+            let mut name = if names.iter().filter(|name| **name == names[i]).count() > 1 {
+                path.segments.iter().map(|s| snake_case(&s.ident)).collect::<Vec<_>>().join("_")
+            } else {
+                names[i].clone()
+            };
+            // paths can still collide once joined, for example `a_b::C` and `a::BC`
+            if idents.iter().any(|ident| *ident == name) {
+                name = format!("{name}_{i}");
+            }
+            idents.push(format_ident!("{name}"));
+        }
+
+        Self {
+            paths: backends.iter().map(|path| quote! {#path}.to_string()).collect(),
+            idents,
+        }
+    }
+
+    // The field holding the backend `path`.
+    fn get(&self, path: &Path) -> &Ident {
+        let path = quote! {#path}.to_string();
+        let i = self.paths.iter().position(|p| *p == path).expect("backends should be collected");
+        &self.idents[i]
+    }
+}
+
+// Convert a type name to snake_case.
+fn snake_case(ident: &Ident) -> String {
+    let mut snake = String::new();
+    for char in ident.to_string().chars() {
         if char.is_uppercase() {
-            if !field_str.is_empty() {
-                field_str.push('_');
+            if !snake.is_empty() {
+                snake.push('_');
             }
-            field_str.push_str(&char.to_lowercase().to_string());
+            snake.push_str(&char.to_lowercase().to_string());
         } else {
-            field_str.push(char);
+            snake.push(char);
         }
     }
+    snake
+}
 
-    format_ident!("{field_str}")
+// The configuration file section of the host at `path`: its type name,
+// without the `Wasi` prefix, in lower case.
+fn section(path: &Path) -> String {
+    let name = path.segments.last().map(|s| s.ident.to_string()).unwrap_or_default();
+    name.strip_prefix("Wasi").unwrap_or(&name).to_lowercase()
 }
 
 fn wasi_ident(path: &Path) -> Ident {
@@ -243,3 +605,31 @@ fn wasi_ident(path: &Path) -> Ident {
     let name = name.replace("Wasi", "qwasr_wasi_").to_lowercase();
     format_ident!("{name}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualified_backend_fields() {
+        let config: Config = syn::parse_str(
+            r#"{
+                hosts: {
+                    WasiKeyValue: { "cache": kv::InMemory, "*": redis::Client },
+                    WasiSql: a::Db,
+                    WasiVault: b::Db,
+                }
+            }"#,
+        )
+        .expect("should parse");
+
+        let fields = BackendFields::new(&config.backends);
+        let idents: Vec<String> = fields.idents.iter().map(ToString::to_string).collect();
+        assert_eq!(idents, ["in_memory", "client", "a_db", "b_db"]);
+
+        let expanded = expand(&config).expect("should expand").to_string();
+        assert!(expanded.contains("a_db : a :: Db"));
+        assert!(expanded.contains("b_db : b :: Db"));
+        assert!(expanded.contains("backends . b_db . clone ()"));
+    }
+}
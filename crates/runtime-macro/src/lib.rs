@@ -8,13 +8,22 @@ use syn::parse_macro_input;
 
 /// Generates the runtime infrastructure based on the configuration.
 ///
+/// Hosts from the bundled `qwasr_wasi_*` crates are wired using the crate's
+/// `qwasr_wasi_view!` macro. Other hosts either name the macro implementing
+/// their view with `=>`, or implement their view trait for any
+/// `T: qwasr::HostContext<Host>`.
+///
 /// # Example
 ///
 /// ```ignore
 /// qwasr::runtime!({
-///     qwasr_wasi_http: WasiHttp,
-///     qwasr_wasi_otel: DefaultOtel,
-///     qwasr_wasi_blobstore: MongoDb,
+///     main: true,
+///     hosts: {
+///         WasiHttp: HttpDefault,
+///         WasiOtel: OtelDefault,
+///         inventory::Inventory: InventoryDb => inventory::inventory_view,
+///         Audit: AuditLog,
+///     }
 /// });
 /// ```
 #[proc_macro]
@@ -1,14 +1,19 @@
+use quote::ToTokens;
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
-use syn::{LitBool, Path, Result, Token};
+use syn::{LitBool, LitStr, Path, Result, Token};
 
 /// Configuration for the runtime macro.
 ///
-/// Parses input in the form of 'host:backend' pairs. For example:
+/// Parses input in the form of 'host:backend' pairs, each optionally followed
+/// by `=> path::to::view_macro`. Hosts opening resources by name can bind
+/// several backends, each to the names matching a pattern. For example:
 /// ```ignore
 /// {
 ///     WasiHttp: HttpDefault,
 ///     WasiOtel: DefaultOtel,
+///     WasiKeyValue: { "cache": KeyValueDefault, "*": Redis },
+///     inventory::Inventory: InventoryDb => inventory::inventory_view,
 ///     ...
 /// }
 /// ```
@@ -34,13 +39,25 @@ impl Parse for Config {
             }
         }
 
-        // deduplicate backends
+        // hosts are linked once, so can only be listed once
+        for (i, host) in hosts.0.iter().enumerate() {
+            let name = host.name();
+            if hosts.0[..i].iter().any(|h| h.name() == name) {
+                return Err(syn::Error::new_spanned(
+                    &host.type_,
+                    format!("host `{name}` is listed more than once"),
+                ));
+            }
+        }
+
+        // deduplicate backends on their full path
         let mut backends = vec![];
-        for host in &hosts.0 {
-            if backends.iter().any(|b: &Path| b.get_ident() == host.backend.get_ident()) {
+        for backend in hosts.0.iter().flat_map(|host| host.backend.paths()) {
+            let path = backend.to_token_stream().to_string();
+            if backends.iter().any(|b: &Path| b.to_token_stream().to_string() == path) {
                 continue;
             }
-            backends.push(host.backend.clone());
+            backends.push(backend.clone());
         }
 
         Ok(Self {
@@ -93,14 +110,127 @@ impl Parse for Hosts {
 /// Information about a WASI host and its configuration.
 pub struct Host {
     pub type_: Path,
-    pub backend: Path,
+    pub backend: Backends,
+
+    /// The macro implementing the host's view for the store context, when
+    /// set explicitly.
+    pub view: Option<Path>,
+}
+
+impl Host {
+    /// The name of the host type, without its module path.
+    pub fn name(&self) -> String {
+        self.type_.segments.last().map(|s| s.ident.to_string()).unwrap_or_default()
+    }
 }
 
 impl Parse for Host {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let type_ = input.parse::<Path>()?;
         input.parse::<Token![:]>()?;
-        let backend = input.parse::<Path>()?;
-        Ok(Self { type_, backend })
+        let backend = input.parse::<Backends>()?;
+        let view = if input.peek(Token![=>]) {
+            input.parse::<Token![=>]>()?;
+            Some(input.parse::<Path>()?)
+        } else {
+            None
+        };
+        Ok(Self { type_, backend, view })
+    }
+}
+
+/// The backend providing a host, or the backends bound to names matching
+/// each pattern.
+pub enum Backends {
+    Single(Path),
+    Named(Vec<(LitStr, Path)>),
+}
+
+impl Backends {
+    /// The backend types providing the host.
+    pub fn paths(&self) -> Vec<&Path> {
+        match self {
+            Self::Single(path) => vec![path],
+            Self::Named(named) => named.iter().map(|(_, path)| path).collect(),
+        }
+    }
+}
+
+impl Parse for Backends {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if !input.peek(syn::token::Brace) {
+            return Ok(Self::Single(input.parse::<Path>()?));
+        }
+
+        let list;
+        let brace = syn::braced!(list in input);
+        let entries = Punctuated::<NamedBackend, Token![,]>::parse_terminated(&list)?;
+
+        let mut named: Vec<(LitStr, Path)> = Vec::new();
+        for NamedBackend(pattern, path) in entries {
+            if named.iter().any(|(p, _)| p.value() == pattern.value()) {
+                return Err(syn::Error::new_spanned(
+                    &pattern,
+                    format!("names matching `{}` are already bound", pattern.value()),
+                ));
+            }
+            named.push((pattern, path));
+        }
+        if named.is_empty() {
+            return Err(syn::Error::new(brace.span.join(), "expected at least one named backend"));
+        }
+
+        Ok(Self::Named(named))
+    }
+}
+
+// A backend bound to names matching a pattern: `"pattern": Backend`.
+struct NamedBackend(LitStr, Path);
+
+impl Parse for NamedBackend {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let pattern = input.parse::<LitStr>()?;
+        input.parse::<Token![:]>()?;
+        Ok(Self(pattern, input.parse::<Path>()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualified_backends() {
+        let config: Config = syn::parse_str(
+            r#"{
+                hosts: {
+                    WasiKeyValue: { "cache": kv::InMemory, "*": redis::Client },
+                    WasiSql: redis::Client,
+                }
+            }"#,
+        )
+        .expect("should parse");
+
+        let backends: Vec<String> =
+            config.backends.iter().map(|b| b.to_token_stream().to_string()).collect();
+        assert_eq!(backends, ["kv :: InMemory", "redis :: Client"]);
+    }
+
+    #[test]
+    fn third_party_backends() {
+        let config: Config = syn::parse_str(
+            "{
+                hosts: {
+                    WasiHttp: HttpDefault,
+                    inventory::Inventory: inventory::InventoryDb => inventory::inventory_view,
+                    audit::Audit: audit::AuditLog,
+                }
+            }",
+        )
+        .expect("should parse");
+
+        let backends: Vec<String> =
+            config.backends.iter().map(|b| b.to_token_stream().to_string()).collect();
+        assert_eq!(backends, ["HttpDefault", "inventory :: InventoryDb", "audit :: AuditLog"]);
     }
 }
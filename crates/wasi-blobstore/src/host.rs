@@ -30,13 +30,14 @@ mod generated {
     });
 }
 
+use std::any::Any;
 use std::fmt::Debug;
 use std::sync::Arc;
 
 use anyhow::Result;
 use bytes::Bytes;
 pub use qwasr::FutureResult;
-use qwasr::{Host, Server, State};
+use qwasr::{Host, Policy, Server, Shutdown, State};
 pub use resource::*;
 use wasmtime::component::{HasData, Linker, ResourceTable};
 use wasmtime_wasi::p2::pipe::MemoryOutputPipe;
@@ -53,7 +54,7 @@ pub type OutgoingValue = MemoryOutputPipe;
 pub type StreamObjectNames = Vec<String>;
 
 /// Host-side service for `wasi:blobstore`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WasiBlobstore;
 
 impl HasData for WasiBlobstore {
@@ -71,7 +72,70 @@ where
     }
 }
 
-impl<S> Server<S> for WasiBlobstore where S: State {}
+impl<S> Server<S> for WasiBlobstore
+where
+    S: State,
+    S::StoreCtx: WasiBlobstoreView + 'static,
+{
+    async fn run(&self, state: &S, shutdown: Shutdown) -> Result<()> {
+        if let Some(container) = &state.options().coredump.container {
+            qwasr::set_core_dump_container(Arc::new(CoreDumpContainer {
+                state: state.clone(),
+                name: container.clone(),
+            }));
+        }
+        shutdown.signalled().await;
+        Ok(())
+    }
+}
+
+// The container guest core dumps are written to, opened with a new store
+// context for each operation.
+struct CoreDumpContainer<S> {
+    state: S,
+    name: String,
+}
+
+impl<S> CoreDumpContainer<S>
+where
+    S: State,
+    S::StoreCtx: WasiBlobstoreView + 'static,
+{
+    // Open the container, creating it if it does not exist.
+    fn open(&self) -> FutureResult<Arc<dyn Container>> {
+        let mut store_ctx = self.state.store();
+        let name = self.name.clone();
+        Box::pin(async move {
+            let ctx = store_ctx.blobstore().ctx;
+            if ctx.container_exists(name.clone()).await? {
+                ctx.get_container(name).await
+            } else {
+                ctx.create_container(name).await
+            }
+        })
+    }
+}
+
+impl<S> qwasr::CoreDumpStore for CoreDumpContainer<S>
+where
+    S: State,
+    S::StoreCtx: WasiBlobstoreView + 'static,
+{
+    fn list(&self) -> FutureResult<Vec<String>> {
+        let container = self.open();
+        Box::pin(async move { container.await?.list_objects().await })
+    }
+
+    fn write(&self, name: String, data: Vec<u8>) -> FutureResult<()> {
+        let container = self.open();
+        Box::pin(async move { container.await?.write_data(name, data).await })
+    }
+
+    fn delete(&self, name: String) -> FutureResult<()> {
+        let container = self.open();
+        Box::pin(async move { container.await?.delete_object(name).await })
+    }
+}
 
 /// A trait which provides internal WASI Blobstore state.
 ///
@@ -89,6 +153,9 @@ pub struct WasiBlobstoreCtxView<'a> {
 
     /// Mutable reference to table used to manage resources.
     pub table: &'a mut ResourceTable,
+
+    /// Capability policy limiting the resources the guest may access.
+    pub policy: &'a Policy,
 }
 
 /// A trait which provides internal WASI Blobstore context.
@@ -109,6 +176,60 @@ pub trait WasiBlobstoreCtx: Debug + Send + Sync + 'static {
     fn container_exists(&self, name: String) -> FutureResult<bool>;
 }
 
+impl<B: WasiBlobstoreCtx + Clone> qwasr::DynamicHost<B> for WasiBlobstore {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn WasiBlobstoreCtx>)
+    }
+}
+
+impl WasiBlobstoreView for qwasr::RuntimeCtx {
+    fn blobstore(&mut self) -> WasiBlobstoreCtxView<'_> {
+        let (ctx, table, policy) = self
+            .context::<Box<dyn WasiBlobstoreCtx>>()
+            .expect("blobstore host context should be registered");
+        WasiBlobstoreCtxView {
+            ctx: ctx.as_mut(),
+            table,
+            policy,
+        }
+    }
+}
+
+impl qwasr::NamedHost for WasiBlobstore {
+    type Ctx = dyn WasiBlobstoreCtx;
+}
+
+// Forward to the backend bound to the name opened by the guest.
+impl WasiBlobstoreCtx for qwasr::NamedBackends<dyn WasiBlobstoreCtx> {
+    fn create_container(&self, name: String) -> FutureResult<Arc<dyn Container>> {
+        match self.get(&name) {
+            Ok(backend) => backend.create_container(name),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+
+    fn get_container(&self, name: String) -> FutureResult<Arc<dyn Container>> {
+        match self.get(&name) {
+            Ok(backend) => backend.get_container(name),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+
+    fn delete_container(&self, name: String) -> FutureResult<()> {
+        match self.get(&name) {
+            Ok(backend) => backend.delete_container(name),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+
+    fn container_exists(&self, name: String) -> FutureResult<bool> {
+        match self.get(&name) {
+            Ok(backend) => backend.container_exists(name),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+}
+
 /// Implementation of the `WasiBlobstoreView` trait for the store context.
 #[macro_export]
 macro_rules! qwasr_wasi_view {
@@ -118,6 +239,7 @@ macro_rules! qwasr_wasi_view {
                 qwasr_wasi_blobstore::WasiBlobstoreCtxView {
                     ctx: &mut self.$field_name,
                     table: &mut self.table,
+                    policy: &self.policy,
                 }
             }
         }
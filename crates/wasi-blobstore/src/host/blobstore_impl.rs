@@ -10,6 +10,7 @@ impl HostWithStore for WasiBlobstore {
         accessor: &Accessor<T, Self>, name: String,
     ) -> Result<Resource<ContainerProxy>> {
         tracing::trace!("create_container: {name}");
+        accessor.with(|mut store| store.get().policy.check("blobstore", "containers", &name))?;
         let container = accessor.with(|mut store| store.get().ctx.create_container(name)).await?;
         let proxy = ContainerProxy(container);
         Ok(accessor.with(|mut store| store.get().table.push(proxy))?)
@@ -19,6 +20,7 @@ impl HostWithStore for WasiBlobstore {
         accessor: &Accessor<T, Self>, name: String,
     ) -> Result<Resource<ContainerProxy>> {
         tracing::trace!("get_container: {name}");
+        accessor.with(|mut store| store.get().policy.check("blobstore", "containers", &name))?;
         let container = accessor.with(|mut store| store.get().ctx.get_container(name)).await?;
         let proxy = ContainerProxy(container);
         Ok(accessor.with(|mut store| store.get().table.push(proxy))?)
@@ -26,11 +28,13 @@ impl HostWithStore for WasiBlobstore {
 
     async fn delete_container<T>(accessor: &Accessor<T, Self>, name: String) -> Result<()> {
         tracing::trace!("delete_container: {name}");
+        accessor.with(|mut store| store.get().policy.check("blobstore", "containers", &name))?;
         accessor.with(|mut store| store.get().ctx.delete_container(name)).await
     }
 
     async fn container_exists<T>(accessor: &Accessor<T, Self>, name: String) -> Result<bool> {
         tracing::trace!("container_exists: {name}");
+        accessor.with(|mut store| store.get().policy.check("blobstore", "containers", &name))?;
         accessor.with(|mut store| store.get().ctx.container_exists(name)).await
     }
 
@@ -4,6 +4,7 @@
 
 mod default_impl;
 
+use std::any::Any;
 use std::fmt::Debug;
 
 use anyhow::Result;
@@ -14,7 +15,7 @@ pub use wasmtime_wasi_config;
 use wasmtime_wasi_config::WasiConfigVariables;
 
 /// Host-side service for `wasi:config`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WasiConfig;
 
 impl HasData for WasiConfig {
@@ -50,6 +51,21 @@ pub trait WasiConfigCtx: Debug + Send + Sync + 'static {
     fn get_config(&self) -> &WasiConfigVariables;
 }
 
+impl<B: WasiConfigCtx + Clone> qwasr::DynamicHost<B> for WasiConfig {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn WasiConfigCtx>)
+    }
+}
+
+impl WasiConfigView for qwasr::RuntimeCtx {
+    fn config(&mut self) -> wasmtime_wasi_config::WasiConfig<'_> {
+        let (ctx, _, _) = self
+            .context::<Box<dyn WasiConfigCtx>>()
+            .expect("config host context should be registered");
+        wasmtime_wasi_config::WasiConfig::from(ctx.get_config())
+    }
+}
+
 /// Implementation of the `WasiConfigView` trait for the store context.
 #[macro_export]
 macro_rules! qwasr_wasi_view {
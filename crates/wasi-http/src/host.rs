@@ -5,14 +5,17 @@
 mod default_impl;
 mod server;
 
+use std::any::Any;
+
 use anyhow::Result;
 pub use default_impl::HttpDefault;
-use qwasr::{Host, Server, State};
+use qwasr::{Host, Server, Shutdown, State, Trace};
+pub use server::handle;
 use wasmtime::component::Linker;
 pub use wasmtime_wasi_http::p3::{WasiHttpCtxView, WasiHttpView};
 
 /// Host-side service for `wasi:http`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WasiHttp;
 
 impl<T> Host<T> for WasiHttp
@@ -22,6 +25,10 @@ where
     fn add_to_linker(linker: &mut Linker<T>) -> Result<()> {
         wasmtime_wasi_http::p3::add_to_linker(linker)
     }
+
+    fn env_vars() -> Vec<String> {
+        vec!["HTTP_ADDR".into()]
+    }
 }
 
 impl<S> Server<S> for WasiHttp
@@ -29,8 +36,21 @@ where
     S: State,
     S::StoreCtx: WasiHttpView,
 {
-    async fn run(&self, state: &S) -> Result<()> {
-        server::serve(state).await
+    async fn run(&self, state: &S, shutdown: Shutdown) -> Result<()> {
+        server::serve(state, shutdown).await
+    }
+
+    async fn replay(&self, state: &S, trace: &Trace, shutdown: Shutdown) -> Option<Result<()>> {
+        if trace.trigger != "http" {
+            return None;
+        }
+        Some(server::replay(state, trace, shutdown).await)
+    }
+}
+
+impl<B: wasmtime_wasi_http::p3::WasiHttpCtx + Clone + 'static> qwasr::DynamicHost<B> for WasiHttp {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn wasmtime_wasi_http::p3::WasiHttpCtx>)
     }
 }
 
@@ -10,9 +10,10 @@ use http::header::{
     UPGRADE,
 };
 use http::{Request, Response};
-use http_body_util::BodyExt;
 use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full};
 use qwasr::Backend;
+use serde::{Deserialize, Serialize};
 use tracing::instrument;
 use wasmtime_wasi::TrappableError;
 use wasmtime_wasi_http::p3::bindings::http::types::ErrorCode;
@@ -45,6 +46,10 @@ impl qwasr::FromEnv for ConnectOptions {
     fn from_env() -> Result<Self> {
         Self::from_env().finalize().context("issue loading connection options")
     }
+
+    fn env_vars() -> Vec<String> {
+        qwasr::requirement_vars(&Self::requirements())
+    }
 }
 
 /// Default implementation for `wasi:http`.
@@ -94,20 +99,29 @@ impl p3::WasiHttpCtx for HttpDefault {
             let client_builder = client_builder.no_proxy();
 
             let client = client_builder.build().map_err(reqwest_error)?;
-
-            // make request
-            let resp = client
-                .request(parts.method, parts.uri.to_string())
-                .body(collected.to_bytes())
-                .send()
-                .await
-                .map_err(reqwest_error)?;
-
-            // process response
-            let converted: Response<reqwest::Body> = resp.into();
-            let (parts, body) = converted.into_parts();
-            let body = body.map_err(reqwest_error).boxed_unsync();
-            let mut response = Response::from_parts(parts, body);
+            let request = client.request(parts.method, parts.uri.to_string());
+            let body = collected.to_bytes();
+
+            // make request, buffering the response when host calls are traced
+            let mut response = if qwasr::is_traced() {
+                let args = (request_args(&request), body.to_vec());
+                let recorded = qwasr::traced("http.send", args, || {
+                    Box::pin(async move {
+                        let resp = request.body(body).send().await.map_err(reqwest_error)?;
+                        RecordedResponse::read(resp).await
+                    })
+                });
+                // recorded errors are replayed as internal errors
+                let recorded = recorded.await.map_err(|e| match e.downcast::<ErrorCode>() {
+                    Ok(code) => code,
+                    Err(e) => internal_error(e),
+                });
+                recorded?.into_response()?
+            } else {
+                let resp = request.body(body).send().await.map_err(reqwest_error)?;
+                let converted: Response<reqwest::Body> = resp.into();
+                converted.map(|body| body.map_err(reqwest_error).boxed_unsync())
+            };
 
             // remove forbidden headers (disallowed by `wasmtime-wasi-http`)
             let headers = response.headers_mut();
@@ -120,6 +134,50 @@ impl p3::WasiHttpCtx for HttpDefault {
     }
 }
 
+// The method and URI of an outbound request, recorded with its body.
+fn request_args(request: &reqwest::RequestBuilder) -> Option<(String, String)> {
+    let request = request.try_clone()?.build().ok()?;
+    Some((request.method().to_string(), request.url().to_string()))
+}
+
+// An outbound response, as recorded in a trace.
+#[derive(Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl RecordedResponse {
+    // Read the response, buffering its body.
+    async fn read(response: reqwest::Response) -> Result<Self> {
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned())
+            })
+            .collect();
+        let body = response.bytes().await.map_err(reqwest_error)?.to_vec();
+        Ok(Self {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    // Rebuild the response for the guest.
+    fn into_response(self) -> Result<Response<UnsyncBoxBody<Bytes, ErrorCode>>, ErrorCode> {
+        let mut builder = Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let body = Full::new(Bytes::from(self.body)).map_err(|never| match never {});
+        builder.body(body.boxed_unsync()).map_err(internal_error)
+    }
+}
+
 fn internal_error(e: impl Display) -> ErrorCode {
     ErrorCode::InternalError(Some(e.to_string()))
 }
@@ -1,8 +1,10 @@
 //! #HTTP Server
 
 use std::clone::Clone;
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::env;
+use std::pin::pin;
 use std::sync::Arc;
 
 use anyhow::{Context, Result, anyhow};
@@ -15,21 +17,23 @@ use hyper::body::Incoming;
 use hyper::header::{FORWARDED, HOST};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
-use qwasr::State;
+use qwasr::{Instances, Shutdown, State, Trace, Tracer};
+use serde::{Deserialize, Serialize};
 use tokio::net::TcpListener;
 use tokio::sync::oneshot;
 use tracing::{Instrument, debug_span};
-use wasmtime::Store;
+use wasmtime::Trap;
 use wasmtime_wasi_http::io::TokioIo;
 use wasmtime_wasi_http::p3::WasiHttpView;
 use wasmtime_wasi_http::p3::bindings::ProxyIndices;
 use wasmtime_wasi_http::p3::bindings::http::types::{self as wasi, ErrorCode};
 
+type IncomingBody = UnsyncBoxBody<Bytes, ErrorCode>;
 type OutgoingBody = UnsyncBoxBody<Bytes, anyhow::Error>;
 
 const HTTP_ADDR: &str = "0.0.0.0:8080";
 
-pub async fn serve<S>(state: &S) -> Result<()>
+pub async fn serve<S>(state: &S, shutdown: Shutdown) -> Result<()>
 where
     S: State,
     S::StoreCtx: WasiHttpView,
@@ -40,49 +44,59 @@ where
     let listener = TcpListener::bind(&addr).await?;
     tracing::info!("{component} http server listening on: {addr}");
 
+    let instances = state
+        .guests()
+        .all()
+        .iter()
+        .map(|guest| (guest.name().to_string(), Instances::new(state, guest)))
+        .collect();
     let handler = Handler {
         state: Arc::new(state.clone()),
+        instances: Arc::new(instances),
+        shutdown: shutdown.clone(),
         component,
     };
 
-    // listen for requests until terminated
+    // listen for requests until shutdown
     loop {
-        let (stream, _) = listener.accept().await?;
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            () = shutdown.signalled() => break,
+        };
         stream.set_nodelay(true)?;
         let stream = TokioIo::new(stream);
         let handler = handler.clone();
+        let signal = shutdown.clone();
 
-        tokio::spawn(async move {
+        shutdown.spawn(async move {
             let mut http1 = http1::Builder::new();
             http1.keep_alive(true);
 
-            if let Err(e) = http1
-                .serve_connection(
-                    stream,
-                    service_fn(move |request| {
-                        let handler = handler.clone();
-                        async move {
-                            let response =
-                                handler.handle(request).await.unwrap_or_else(|_e| internal_error());
-
-                            // track server error responses
-                            if response.status() >= StatusCode::INTERNAL_SERVER_ERROR {
-                                tracing::error!(
-                                    monotonic_counter.processing_errors = 1,
-                                    service = %handler.component,
-                                    error = format!("{response:?}"),
-                                );
-                            }
-                            Ok::<_, Infallible>(response)
-                        }
-                    }),
-                )
-                .await
-            {
+            let connection = http1.serve_connection(
+                stream,
+                service_fn(move |request| {
+                    let handler = handler.clone();
+                    async move { Ok::<_, Infallible>(handler.route(request).await) }
+                }),
+            );
+            let mut connection = pin!(connection);
+
+            // on shutdown, finish in-flight requests then close the connection
+            let result = tokio::select! {
+                result = connection.as_mut() => result,
+                () = signal.signalled() => {
+                    connection.as_mut().graceful_shutdown();
+                    connection.await
+                }
+            };
+            if let Err(e) = result {
                 tracing::error!("connection error: {e:?}");
             }
         });
     }
+
+    tracing::info!("{} http server stopped accepting connections", handler.component);
+    Ok(())
 }
 
 #[derive(Clone)]
@@ -92,6 +106,8 @@ where
     S::StoreCtx: WasiHttpView,
 {
     state: Arc<S>,
+    instances: Arc<HashMap<String, Arc<Instances<S>>>>,
+    shutdown: Shutdown,
     component: String,
 }
 
@@ -100,33 +116,105 @@ where
     S: State,
     S::StoreCtx: WasiHttpView,
 {
-    // Forward request to the wasm Guest.
-    async fn handle(
-        &self, request: hyper::Request<Incoming>,
-    ) -> Result<hyper::Response<OutgoingBody>> {
+    // Route the request to the guest component serving its host or path.
+    async fn route(&self, request: hyper::Request<Incoming>) -> hyper::Response<OutgoingBody> {
         tracing::debug!("handling request: {request:?}");
 
         // prepare wasmtime http request and response
-        let request = fix_request(request).context("preparing request")?;
+        let request = match fix_request(request).context("preparing request") {
+            Ok(request) => request,
+            Err(e) => return error_response(&e),
+        };
+
+        let uri = request.uri();
+        let Some(instances) = self
+            .state
+            .guests()
+            .route_http(uri.host(), uri.path())
+            .and_then(|guest| self.instances.get(guest.name()))
+        else {
+            tracing::debug!("no component routed for {uri}");
+            return html_response(StatusCode::NOT_FOUND, "No component serves this request");
+        };
+
+        let response = self.record(instances, request).await.unwrap_or_else(|e| error_response(&e));
+
+        // track server error responses
+        if response.status() >= StatusCode::INTERNAL_SERVER_ERROR {
+            tracing::error!(
+                monotonic_counter.processing_errors = 1,
+                service = %instances.guest().name(),
+                error = format!("{response:?}"),
+            );
+        }
+        response
+    }
+
+    // Forward request to the wasm guest, recording the request and the
+    // guest's host calls when enabled.
+    async fn record(
+        &self, instances: &Instances<S>, request: hyper::Request<Incoming>,
+    ) -> Result<hyper::Response<OutgoingBody>> {
+        let request = request.map(|body| body.map_err(ErrorCode::from_hyper_request_error));
+        let id = request.headers().get("x-request-id").and_then(|id| id.to_str().ok());
+        let Some(tracer) =
+            qwasr::start_recording(&*self.state, instances.guest().name(), "http", id)
+        else {
+            return self.handle(instances, request.map(BodyExt::boxed_unsync), None).await;
+        };
+
+        // buffer the body so the request can be recorded
+        let (parts, body) = request.into_parts();
+        let body = body.collect().await.context("reading request body")?.to_bytes();
+        let recorded = RecordedRequest {
+            method: parts.method.to_string(),
+            uri: parts.uri.to_string(),
+            headers: parts
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned())
+                })
+                .collect(),
+            body: body.to_vec(),
+        };
+        tracer.set_request(&recorded);
+        self.handle(instances, recorded.into_request()?, Some(tracer)).await
+    }
 
+    // Forward request to the wasm guest, tracing its host calls with
+    // `tracer`.
+    async fn handle(
+        &self, instances: &Instances<S>, request: http::Request<IncomingBody>,
+        tracer: Option<Tracer>,
+    ) -> Result<hyper::Response<OutgoingBody>> {
         // instantiate the guest and get the proxy
-        let instance_pre = self.state.instance_pre();
-        let store_data = self.state.store();
-        let mut store = Store::new(instance_pre.engine(), store_data);
-        let indices = ProxyIndices::new(instance_pre)?;
-        let instance = instance_pre.instantiate_async(&mut store).await?;
+        let (mut store, instance, instance_pre) = instances.get().await?;
+        let profile = qwasr::start_profile(
+            &*self.state,
+            instances.guest(),
+            &instance_pre,
+            &mut store,
+            "http",
+        );
+        let indices = ProxyIndices::new(&instance_pre)?;
         let proxy = indices.load(&mut store, &instance)?;
 
         let (sender, receiver) = oneshot::channel();
-
-        tokio::spawn(async move {
-            let guest_result = store
+        let state = Arc::clone(&self.state);
+        let component = instances.guest().name().to_string();
+        let request_id = request
+            .headers()
+            .get("x-request-id")
+            .and_then(|id| id.to_str().ok())
+            .map(ToString::to_string);
+        let tracked = qwasr::Invocation::start(&component, "http");
+
+        let guest = self.shutdown.spawn(async move {
+            let invocation = store
                 .run_concurrent(async |store| {
-                    // convert hyper::Request to wasi::Request
-                    let (parts, body) = request.into_parts();
-                    let body = body.map_err(ErrorCode::from_hyper_request_error);
-                    let http_req = http::Request::from_parts(parts, body);
-                    let (request, io_result) = wasi::Request::from_http(http_req);
+                    // convert http::Request to wasi::Request
+                    let (request, io_result) = wasi::Request::from_http(request);
 
                     // forward request to guest
                     let (wasi_resp, task) = proxy.handle(store, request).await??;
@@ -137,27 +225,140 @@ where
 
                     anyhow::Ok(())
                 })
-                .instrument(debug_span!("http-request"))
-                .await?;
+                .instrument(debug_span!("http-request"));
+            let guest_result = match &tracer {
+                Some(tracer) => tracer.scope(invocation).await,
+                None => invocation.await,
+            };
+            qwasr::record_fuel(&store, state.options(), &component, "http");
+            if let Some(profile) = profile {
+                profile.finish();
+            }
+            if let Some(tracer) = tracer {
+                tracer.finish();
+            }
+
+            let guest_result = guest_result.and_then(|result| result);
+            tracked.finish::<S>(&mut store, guest_result.as_ref().err());
 
             if let Err(e) = guest_result {
+                let options = state.options();
+                qwasr::save_core_dump(&mut store, &e, options, &component, request_id.as_deref())
+                    .await;
                 tracing::error!("Guest error: {e:?}");
                 return Err(e);
             }
 
-            // write_profile(&mut store);
-            // drop(epoch_thread);
-
             Ok(())
         });
 
-        let response = receiver.await?.map(|body| body.map_err(Into::into).boxed_unsync());
+        // when the guest fails before responding, return its error
+        let Ok(response) = receiver.await else {
+            guest.await??;
+            return Err(anyhow!("guest did not send a response"));
+        };
+        let response = response.map(|body| body.map_err(Into::into).boxed_unsync());
         tracing::debug!("received response: {response:?}");
 
         Ok(response)
     }
 }
 
+/// Handle `request` with the guest routed its host or path, in-process,
+/// returning the guest's response with its body collected.
+///
+/// Used to drive guests from tests, without an HTTP server. Requests without
+/// a scheme and authority are sent as `http://localhost`.
+///
+/// # Errors
+///
+/// Will fail if the request cannot be prepared for the guest, or the
+/// response body cannot be read. A guest failing to respond returns a server
+/// error response, as it would when served.
+pub async fn handle<S, B>(state: &S, request: http::Request<B>) -> Result<http::Response<Bytes>>
+where
+    S: State,
+    S::StoreCtx: WasiHttpView,
+    B: Into<Bytes>,
+{
+    let (mut parts, body) = request.into_parts();
+    if parts.uri.authority().is_none() {
+        let p_and_q = parts.uri.path_and_query().map_or("/", PathAndQuery::as_str);
+        parts.uri = format!("http://localhost{p_and_q}").parse()?;
+    }
+    let body = Full::new(body.into()).map_err(|never| match never {});
+    let request = http::Request::from_parts(parts, body.boxed_unsync());
+
+    let uri = request.uri();
+    let response = if let Some(guest) = state.guests().route_http(uri.host(), uri.path()) {
+        let handler = Handler {
+            state: Arc::new(state.clone()),
+            instances: Arc::default(),
+            shutdown: Shutdown::new(),
+            component: guest.name().to_string(),
+        };
+        let instances = state.instances(guest);
+        handler.handle(&instances, request, None).await.unwrap_or_else(|e| error_response(&e))
+    } else {
+        html_response(StatusCode::NOT_FOUND, "No component serves this request")
+    };
+
+    let (parts, body) = response.into_parts();
+    let body = body.collect().await.context("reading response body")?.to_bytes();
+    Ok(http::Response::from_parts(parts, body))
+}
+
+// Replay the request recorded in `trace`, printing the guest's response.
+pub async fn replay<S>(state: &S, trace: &Trace, shutdown: Shutdown) -> Result<()>
+where
+    S: State,
+    S::StoreCtx: WasiHttpView,
+{
+    let guest = trace.guest(state.guests())?;
+    let recorded: RecordedRequest = trace.request()?;
+    tracing::info!("replaying {} {} to {}", recorded.method, recorded.uri, guest.name());
+
+    let handler = Handler {
+        state: Arc::new(state.clone()),
+        instances: Arc::default(),
+        shutdown,
+        component: guest.name().to_string(),
+    };
+    let instances = Instances::new(state, guest);
+    let tracer = Tracer::replay(trace.clone());
+    let response = handler.handle(&instances, recorded.into_request()?, Some(tracer)).await?;
+
+    let (parts, body) = response.into_parts();
+    let body = body.collect().await?.to_bytes();
+    println!("{:?} {}", parts.version, parts.status);
+    for (name, value) in &parts.headers {
+        println!("{name}: {}", String::from_utf8_lossy(value.as_bytes()));
+    }
+    println!("\n{}", String::from_utf8_lossy(&body));
+    Ok(())
+}
+
+// An inbound request, as recorded in a trace.
+#[derive(Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    uri: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    // Rebuild the request for the guest.
+    fn into_request(self) -> Result<http::Request<IncomingBody>> {
+        let mut builder = http::Request::builder().method(self.method.as_str()).uri(&self.uri);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let body = Full::new(Bytes::from(self.body)).map_err(|never| match never {});
+        builder.body(body.boxed_unsync()).context("rebuilding recorded request")
+    }
+}
+
 // Prepare the request for the guest.
 fn fix_request(mut request: hyper::Request<Incoming>) -> Result<hyper::Request<Incoming>> {
     // let req_id = self.next_id.fetch_add(1, Ordering::Relaxed);
@@ -194,26 +395,39 @@ fn fix_request(mut request: hyper::Request<Incoming>) -> Result<hyper::Request<I
     Ok(request)
 }
 
-const BODY: &str = r"<!doctype html>
+// Build an HTML error response for a failed guest invocation. Guests trapped
+// for exceeding their deadline are reported as a gateway timeout.
+fn error_response(e: &anyhow::Error) -> hyper::Response<OutgoingBody> {
+    let (status, message) = match e.downcast_ref::<Trap>() {
+        Some(Trap::Interrupt) => (StatusCode::GATEWAY_TIMEOUT, "Guest timed out"),
+        Some(Trap::OutOfFuel) => (StatusCode::INTERNAL_SERVER_ERROR, "Guest exceeded fuel budget"),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "Guest error"),
+    };
+    html_response(status, message)
+}
+
+// Build an HTML response with the status and message.
+fn html_response(status: StatusCode, message: &str) -> hyper::Response<OutgoingBody> {
+    let body = format!(
+        r"<!doctype html>
 <html>
 <head>
-    <title>500 Internal Server Error</title>
+    <title>{status}</title>
 </head>
 <body>
     <center>
-        <h1>500 Internal Server Error</h1>
+        <h1>{status}</h1>
         <hr>
-        <pre>Guest error</pre>
+        <pre>{message}</pre>
     </center>
 </body>
-</html>";
-
-fn internal_error() -> hyper::Response<OutgoingBody> {
-    let body = Full::new(Bytes::from(BODY)).map_err(Into::into).boxed_unsync();
+</html>"
+    );
+    let body = Full::new(Bytes::from(body)).map_err(Into::into).boxed_unsync();
 
     hyper::Response::builder()
-        .status(500)
+        .status(status)
         .header("Content-Type", "text/html; charset=UTF-8")
         .body(body)
-        .expect("should build internal error response")
+        .expect("should build error response")
 }
@@ -26,11 +26,12 @@ mod generated {
     });
 }
 
+use std::any::Any;
 use std::fmt::Debug;
 use std::sync::Arc;
 
 pub use qwasr::FutureResult;
-use qwasr::{Host, Server, State};
+use qwasr::{Host, Policy, Server, State};
 use wasmtime::component::{HasData, Linker};
 use wasmtime_wasi::ResourceTable;
 
@@ -39,7 +40,7 @@ use self::generated::wasi::identity::credentials;
 pub use self::resource::*;
 
 /// Host-side service for `wasi:identity`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WasiIdentity;
 
 impl HasData for WasiIdentity {
@@ -73,6 +74,9 @@ pub struct WasiIdentityCtxView<'a> {
 
     /// Mutable reference to table used to manage resources.
     pub table: &'a mut ResourceTable,
+
+    /// Capability policy limiting the resources the guest may access.
+    pub policy: &'a Policy,
 }
 
 /// A trait which provides internal WASI Identity context.
@@ -84,6 +88,39 @@ pub trait WasiIdentityCtx: Debug + Send + Sync + 'static {
     fn get_identity(&self, name: String) -> FutureResult<Arc<dyn Identity>>;
 }
 
+impl<B: WasiIdentityCtx + Clone> qwasr::DynamicHost<B> for WasiIdentity {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn WasiIdentityCtx>)
+    }
+}
+
+impl WasiIdentityView for qwasr::RuntimeCtx {
+    fn identity(&mut self) -> WasiIdentityCtxView<'_> {
+        let (ctx, table, policy) = self
+            .context::<Box<dyn WasiIdentityCtx>>()
+            .expect("identity host context should be registered");
+        WasiIdentityCtxView {
+            ctx: ctx.as_mut(),
+            table,
+            policy,
+        }
+    }
+}
+
+impl qwasr::NamedHost for WasiIdentity {
+    type Ctx = dyn WasiIdentityCtx;
+}
+
+// Forward to the backend bound to the name opened by the guest.
+impl WasiIdentityCtx for qwasr::NamedBackends<dyn WasiIdentityCtx> {
+    fn get_identity(&self, name: String) -> FutureResult<Arc<dyn Identity>> {
+        match self.get(&name) {
+            Ok(backend) => backend.get_identity(name),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+}
+
 /// Implementation of the `WasiIdentityView` trait for the store context.
 #[macro_export]
 macro_rules! qwasr_wasi_view {
@@ -93,6 +130,7 @@ macro_rules! qwasr_wasi_view {
                 qwasr_wasi_identity::WasiIdentityCtxView {
                     ctx: &mut self.$field_name,
                     table: &mut self.table,
+                    policy: &self.policy,
                 }
             }
         }
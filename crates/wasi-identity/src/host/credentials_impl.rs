@@ -14,6 +14,9 @@ impl HostWithStore for WasiIdentity {
     async fn get_identity<T>(
         accessor: &Accessor<T, Self>, name: String,
     ) -> Result<Resource<IdentityProxy>> {
+        accessor
+            .with(|mut store| store.get().policy.check("identity", "identities", &name))
+            .map_err(|e| Error::InternalFailure(e.to_string()))?;
         let identity = accessor.with(|mut store| store.get().ctx.get_identity(name)).await?;
         let proxy = IdentityProxy(identity);
         Ok(accessor.with(|mut store| store.get().table.push(proxy))?)
@@ -34,6 +34,10 @@ impl qwasr::FromEnv for ConnectOptions {
     fn from_env() -> Result<Self> {
         Self::from_env().finalize().context("issue loading connection options")
     }
+
+    fn env_vars() -> Vec<String> {
+        qwasr::requirement_vars(&Self::requirements())
+    }
 }
 
 /// Default implementation for `wasi:identity`.
@@ -50,6 +54,10 @@ impl Backend for IdentityDefault {
         let token_manager = TokenManager::new(options);
         Ok(Self { token_manager })
     }
+
+    async fn health_check(&self) -> Result<()> {
+        self.token_manager.token(&[]).await.map(|_| ())
+    }
 }
 
 impl WasiIdentityCtx for IdentityDefault {
@@ -5,6 +5,7 @@ mod batch_impl;
 mod default_impl;
 mod resource;
 mod store_impl;
+mod traced;
 
 mod generated {
     pub use self::wasi::keyvalue::store::Error;
@@ -26,11 +27,12 @@ mod generated {
     });
 }
 
+use std::any::Any;
 use std::fmt::Debug;
 use std::sync::Arc;
 
 pub use qwasr::FutureResult;
-use qwasr::{Host, Server, State};
+use qwasr::{Host, Policy, Server, State};
 use wasmtime::component::{HasData, Linker, ResourceTableError};
 use wasmtime_wasi::ResourceTable;
 
@@ -43,7 +45,7 @@ pub use self::resource::*;
 pub type Result<T, E = Error> = anyhow::Result<T, E>;
 
 /// Host-side service for `wasi:keyvalue`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WasiKeyValue;
 
 impl HasData for WasiKeyValue {
@@ -79,6 +81,9 @@ pub struct WasiKeyValueCtxView<'a> {
 
     /// Mutable reference to table used to manage resources.
     pub table: &'a mut ResourceTable,
+
+    /// Capability policy limiting the resources the guest may access.
+    pub policy: &'a Policy,
 }
 
 /// A trait which provides internal WASI Key-Value context.
@@ -96,6 +101,39 @@ impl From<ResourceTableError> for Error {
     }
 }
 
+impl<B: WasiKeyValueCtx + Clone> qwasr::DynamicHost<B> for WasiKeyValue {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn WasiKeyValueCtx>)
+    }
+}
+
+impl WasiKeyValueView for qwasr::RuntimeCtx {
+    fn keyvalue(&mut self) -> WasiKeyValueCtxView<'_> {
+        let (ctx, table, policy) = self
+            .context::<Box<dyn WasiKeyValueCtx>>()
+            .expect("key-value host context should be registered");
+        WasiKeyValueCtxView {
+            ctx: ctx.as_mut(),
+            table,
+            policy,
+        }
+    }
+}
+
+impl qwasr::NamedHost for WasiKeyValue {
+    type Ctx = dyn WasiKeyValueCtx;
+}
+
+// Forward to the backend bound to the name opened by the guest.
+impl WasiKeyValueCtx for qwasr::NamedBackends<dyn WasiKeyValueCtx> {
+    fn open_bucket(&self, identifier: String) -> FutureResult<Arc<dyn Bucket>> {
+        match self.get(&identifier) {
+            Ok(backend) => backend.open_bucket(identifier),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+}
+
 /// Implementation of the `WasiKeyValueView` trait for the store context.
 #[macro_export]
 macro_rules! qwasr_wasi_view {
@@ -105,6 +143,7 @@ macro_rules! qwasr_wasi_view {
                 qwasr_wasi_keyvalue::WasiKeyValueCtxView {
                     ctx: &mut self.$field_name,
                     table: &mut self.table,
+                    policy: &self.policy,
                 }
             }
         }
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use anyhow::Context;
 use wasmtime::component::{Access, Accessor, Resource};
 
@@ -6,14 +8,29 @@ use crate::host::generated::wasi::keyvalue::store::{
 };
 use crate::host::resource::BucketProxy;
 use crate::host::store::{Host, HostBucket};
+use crate::host::traced::TracedBucket;
 use crate::host::{Result, WasiKeyValue, WasiKeyValueCtxView};
 
 impl HostWithStore for WasiKeyValue {
     async fn open<T>(
         accessor: &Accessor<T, Self>, identifier: String,
     ) -> Result<Resource<BucketProxy>> {
-        let bucket = accessor.with(|mut store| store.get().ctx.open_bucket(identifier)).await?;
-        let proxy = BucketProxy(bucket);
+        if !accessor.with(|mut store| store.get().policy.allows("keyvalue", "buckets", &identifier))
+        {
+            return Err(Error::AccessDenied);
+        }
+        let bucket = accessor
+            .with(|mut store| {
+                let ctx = store.get().ctx;
+                qwasr::traced_open("keyvalue.open", identifier.clone(), || {
+                    ctx.open_bucket(identifier.clone())
+                })
+            })
+            .await?;
+        let proxy = BucketProxy(Arc::new(TracedBucket {
+            name: identifier,
+            bucket,
+        }));
         Ok(accessor.with(|mut store| store.get().table.push(proxy))?)
     }
 }
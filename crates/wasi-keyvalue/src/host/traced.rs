@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use crate::host::resource::{Bucket, FutureResult};
+
+/// A bucket whose calls are recorded or replayed when the guest invocation is
+/// traced. The backend's bucket is `None` when replaying.
+#[derive(Debug)]
+pub struct TracedBucket {
+    pub name: String,
+    pub bucket: Option<Arc<dyn Bucket>>,
+}
+
+impl TracedBucket {
+    // Call the backend's bucket.
+    fn call<T: Send + 'static>(
+        &self, call: impl FnOnce(&dyn Bucket) -> FutureResult<T>,
+    ) -> FutureResult<T> {
+        match &self.bucket {
+            Some(bucket) => call(bucket.as_ref()),
+            None => Box::pin(async { Err(anyhow!("bucket is not connected when replaying")) }),
+        }
+    }
+}
+
+impl Bucket for TracedBucket {
+    fn name(&self) -> &'static str {
+        self.bucket.as_ref().map_or("replayed", |bucket| bucket.name())
+    }
+
+    fn get(&self, key: String) -> FutureResult<Option<Vec<u8>>> {
+        qwasr::traced("keyvalue.get", (&self.name, key.clone()), || {
+            self.call(|bucket| bucket.get(key))
+        })
+    }
+
+    fn set(&self, key: String, value: Vec<u8>) -> FutureResult<()> {
+        qwasr::traced("keyvalue.set", (&self.name, key.clone(), value.clone()), || {
+            self.call(|bucket| bucket.set(key, value))
+        })
+    }
+
+    fn delete(&self, key: String) -> FutureResult<()> {
+        qwasr::traced("keyvalue.delete", (&self.name, key.clone()), || {
+            self.call(|bucket| bucket.delete(key))
+        })
+    }
+
+    fn exists(&self, key: String) -> FutureResult<bool> {
+        qwasr::traced("keyvalue.exists", (&self.name, key.clone()), || {
+            self.call(|bucket| bucket.exists(key))
+        })
+    }
+
+    fn keys(&self) -> FutureResult<Vec<String>> {
+        qwasr::traced("keyvalue.keys", &self.name, || self.call(|bucket| bucket.keys()))
+    }
+}
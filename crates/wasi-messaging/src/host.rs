@@ -3,6 +3,7 @@ mod producer_impl;
 mod request_reply_impl;
 mod resource;
 mod server;
+mod traced;
 mod types_impl;
 
 mod generated {
@@ -34,11 +35,12 @@ mod generated {
     });
 }
 
+use std::any::Any;
 use std::fmt::Debug;
 use std::sync::Arc;
 
 pub use qwasr::FutureResult;
-use qwasr::{Host, Server, State};
+use qwasr::{Host, Policy, Server, Shutdown, State, Trace};
 use wasmtime::component::{HasData, Linker};
 use wasmtime_wasi::{ResourceTable, ResourceTableError};
 
@@ -47,12 +49,13 @@ pub use self::generated::Messaging;
 pub use self::generated::wasi::messaging::types::Error;
 use self::generated::wasi::messaging::{producer, request_reply, types};
 pub use self::resource::*;
+pub use self::server::deliver;
 
 /// Result type for messaging operations.
 pub type Result<T, E = Error> = anyhow::Result<T, E>;
 
 /// Host-side service for `wasi:messaging`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WasiMessaging;
 
 impl HasData for WasiMessaging {
@@ -75,8 +78,15 @@ where
     S: State,
     S::StoreCtx: WasiMessagingView,
 {
-    async fn run(&self, state: &S) -> anyhow::Result<()> {
-        server::run(state).await
+    async fn run(&self, state: &S, shutdown: Shutdown) -> anyhow::Result<()> {
+        server::run(state, shutdown).await
+    }
+
+    async fn replay(&self, state: &S, trace: &Trace, _: Shutdown) -> Option<anyhow::Result<()>> {
+        if trace.trigger != "messaging" {
+            return None;
+        }
+        Some(server::replay(state, trace).await)
     }
 }
 
@@ -96,6 +106,9 @@ pub struct WasiMessagingCtxView<'a> {
 
     /// Mutable reference to table used to manage resources.
     pub table: &'a mut ResourceTable,
+
+    /// Capability policy limiting the resources the guest may access.
+    pub policy: &'a Policy,
 }
 
 /// A trait which provides internal WASI Messaging context.
@@ -176,6 +189,25 @@ impl From<anyhow::Error> for Error {
     }
 }
 
+impl<B: WasiMessagingCtx + Clone> qwasr::DynamicHost<B> for WasiMessaging {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn WasiMessagingCtx>)
+    }
+}
+
+impl WasiMessagingView for qwasr::RuntimeCtx {
+    fn messaging(&mut self) -> WasiMessagingCtxView<'_> {
+        let (ctx, table, policy) = self
+            .context::<Box<dyn WasiMessagingCtx>>()
+            .expect("messaging host context should be registered");
+        WasiMessagingCtxView {
+            ctx: ctx.as_mut(),
+            table,
+            policy,
+        }
+    }
+}
+
 /// Implementation of the `WasiMessagingView` trait for the store context.
 #[macro_export]
 macro_rules! qwasr_wasi_view {
@@ -185,6 +217,7 @@ macro_rules! qwasr_wasi_view {
                 qwasr_wasi_messaging::WasiMessagingCtxView {
                     ctx: &mut self.$field_name,
                     table: &mut self.table,
+                    policy: &self.policy,
                 }
             }
         }
@@ -1,7 +1,7 @@
 use wasmtime::component::{Accessor, Resource};
 
 use crate::host::generated::wasi::messaging::producer::{Host, HostWithStore};
-use crate::host::generated::wasi::messaging::types::Topic;
+use crate::host::generated::wasi::messaging::types::{Error, Topic};
 use crate::host::resource::{ClientProxy, MessageProxy};
 use crate::host::types_impl::{get_client, get_message};
 use crate::host::{Result, WasiMessaging, WasiMessagingCtxView};
@@ -11,6 +11,9 @@ impl HostWithStore for WasiMessaging {
         accessor: &Accessor<T, Self>, c: Resource<ClientProxy>, topic: Topic,
         message: Resource<MessageProxy>,
     ) -> Result<()> {
+        accessor
+            .with(|mut store| store.get().policy.check("messaging", "topics", &topic))
+            .map_err(|e| Error::PermissionDenied(e.to_string()))?;
         let client = get_client(accessor, &c)?;
         let msg = get_message(accessor, &message)?;
         client.send(topic, msg).await?;
@@ -5,7 +5,7 @@ use wasmtime::component::{Access, Accessor, Resource};
 use crate::host::generated::wasi::messaging::request_reply::{
     Host, HostRequestOptions, HostRequestOptionsWithStore, HostWithStore,
 };
-use crate::host::generated::wasi::messaging::types::Topic;
+use crate::host::generated::wasi::messaging::types::{Error, Topic};
 use crate::host::resource::{ClientProxy, MessageProxy, RequestOptions};
 use crate::host::types_impl::{get_client, get_message};
 use crate::host::{Result, WasiMessaging, WasiMessagingCtxView};
@@ -15,6 +15,9 @@ impl HostWithStore for WasiMessaging {
         accessor: &Accessor<T, Self>, c: Resource<ClientProxy>, topic: Topic,
         message: Resource<MessageProxy>, options: Option<Resource<RequestOptions>>,
     ) -> Result<Vec<Resource<MessageProxy>>> {
+        accessor
+            .with(|mut store| store.get().policy.check("messaging", "topics", &topic))
+            .map_err(|e| Error::PermissionDenied(e.to_string()))?;
         let client = get_client(accessor, &c)?;
         let request = get_message(accessor, &message)?;
         let options = accessor.with(|mut access| {
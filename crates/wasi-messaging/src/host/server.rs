@@ -1,17 +1,20 @@
+use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use futures::StreamExt;
-use qwasr::State;
+use qwasr::{Instances, Shutdown, State, Trace, Tracer};
 use tracing::{Instrument, debug_span, instrument};
-use wasmtime::Store;
+use wasmtime::Trap;
 
 use crate::host::WasiMessagingView;
 use crate::host::generated::Messaging;
 use crate::host::resource::{MessageProxy, Subscriptions};
+use crate::host::traced::RecordedMessage;
 
 #[instrument("messaging-server", skip(state))]
-pub async fn run<S>(state: &S) -> Result<()>
+pub async fn run<S>(state: &S, shutdown: Shutdown) -> Result<()>
 where
     S: State,
     S::StoreCtx: WasiMessagingView,
@@ -19,29 +22,124 @@ where
     let component = env::var("COMPONENT").unwrap_or_else(|_| "unknown".into());
     tracing::info!("starting messaging server for: {component}");
 
+    let instances = state
+        .guests()
+        .all()
+        .iter()
+        .map(|guest| (guest.name().to_string(), Instances::new(state, guest)))
+        .collect();
     let handler = Handler {
         state: state.clone(),
+        instances: Arc::new(instances),
         component,
     };
     let mut stream = handler.subscriptions().await?;
 
-    while let Some(message) = stream.next().await {
-        let handler = handler.clone();
-        tokio::spawn(async move {
-            tracing::info!(monotonic_counter.message_counter = 1, service = %handler.component);
-
-            if let Err(e) = handler.handle(message.clone()).await {
-                tracing::error!("issue processing message: {e}");
-                tracing::error!(
-                    monotonic_counter.processing_errors = 1,
-                    service = %handler.component,
-                    topic = %message.topic(),
-                    error = %e,
-                );
+    // process messages until shutdown
+    loop {
+        let message = tokio::select! {
+            message = stream.next() => message,
+            () = shutdown.signalled() => break,
+        };
+        let Some(message) = message else {
+            if shutdown.is_signalled() {
+                break;
             }
-        });
+            return Err(anyhow!("subscription stream ended"));
+        };
+
+        // deliver the message to each component routed its topic
+        let guests = handler.state.guests().route_message(&message.topic());
+        if guests.is_empty() {
+            tracing::debug!("no component routed for topic {}", message.topic());
+        }
+        for guest in guests {
+            let Some(instances) = handler.instances.get(guest.name()).cloned() else {
+                continue;
+            };
+            let handler = handler.clone();
+            let message = message.clone();
+
+            shutdown.spawn(async move {
+                let component = instances.guest().name();
+                tracing::info!(monotonic_counter.message_counter = 1, service = %component);
+
+                if let Err(e) = handler.handle(&instances, message.clone()).await {
+                    match e.downcast_ref::<Trap>() {
+                        Some(Trap::Interrupt) => {
+                            tracing::error!("guest timed out processing message");
+                        }
+                        Some(Trap::OutOfFuel) => {
+                            tracing::error!("guest exceeded fuel budget processing message");
+                        }
+                        _ => tracing::error!("issue processing message: {e}"),
+                    }
+                    tracing::error!(
+                        monotonic_counter.processing_errors = 1,
+                        service = %component,
+                        topic = %message.topic(),
+                        error = %e,
+                    );
+                }
+            });
+        }
     }
 
+    tracing::info!("messaging server for {} stopped accepting messages", handler.component);
+    Ok(())
+}
+
+// Replay the message delivery recorded in `trace`.
+pub async fn replay<S>(state: &S, trace: &Trace) -> Result<()>
+where
+    S: State,
+    S::StoreCtx: WasiMessagingView,
+{
+    let guest = trace.guest(state.guests())?;
+    let message: RecordedMessage = trace.request()?;
+    tracing::info!("replaying message on {} to {}", message.topic, guest.name());
+
+    let handler = Handler {
+        state: state.clone(),
+        instances: Arc::default(),
+        component: guest.name().to_string(),
+    };
+    let instances = Instances::new(state, guest);
+    let message = MessageProxy(Arc::new(message));
+    handler.invoke(&instances, message, Some(Tracer::replay(trace.clone()))).await
+}
+
+/// Deliver `message` to each guest routed its topic, in-process, returning
+/// once every guest has handled it.
+///
+/// Used to drive guests from tests, without a messaging backend delivering
+/// the message.
+///
+/// # Errors
+///
+/// Will fail if no guest is routed the message's topic, or a guest fails to
+/// handle the message.
+pub async fn deliver<S>(state: &S, message: MessageProxy) -> Result<()>
+where
+    S: State,
+    S::StoreCtx: WasiMessagingView,
+{
+    let guests = state.guests().route_message(&message.topic());
+    if guests.is_empty() {
+        bail!("no component routed for topic {}", message.topic());
+    }
+    for guest in guests {
+        let handler = Handler {
+            state: state.clone(),
+            instances: Arc::default(),
+            component: guest.name().to_string(),
+        };
+        let instances = state.instances(guest);
+        handler
+            .handle(&instances, message.clone())
+            .await
+            .with_context(|| format!("delivering message to {}", guest.name()))?;
+    }
     Ok(())
 }
 
@@ -52,6 +150,7 @@ where
     S::StoreCtx: WasiMessagingView,
 {
     state: S,
+    instances: Arc<HashMap<String, Arc<Instances<S>>>>,
     component: String,
 }
 
@@ -60,34 +159,70 @@ where
     S: State,
     S::StoreCtx: WasiMessagingView,
 {
-    // Forward message to the wasm guest.
-    async fn handle(&self, message: MessageProxy) -> Result<()> {
-        let mut store_data = self.state.store();
-        let msg_res = store_data
+    // Forward message to the wasm guest, recording its host calls when
+    // enabled.
+    async fn handle(&self, instances: &Instances<S>, message: MessageProxy) -> Result<()> {
+        let component = instances.guest().name();
+        let tracer = qwasr::start_recording(&self.state, component, "messaging", None);
+        if let Some(tracer) = &tracer {
+            tracer.set_request(&RecordedMessage::from(&**message));
+        }
+        self.invoke(instances, message, tracer).await
+    }
+
+    // Invoke the guest with the message, tracing its host calls with
+    // `tracer`.
+    async fn invoke(
+        &self, instances: &Instances<S>, message: MessageProxy, tracer: Option<Tracer>,
+    ) -> Result<()> {
+        let (mut store, instance, instance_pre) = instances.get().await?;
+        let profile = qwasr::start_profile(
+            &self.state,
+            instances.guest(),
+            &instance_pre,
+            &mut store,
+            "messaging",
+        );
+        let msg_res = store
+            .data_mut()
             .messaging()
             .table
             .push(message)
             .map_err(|e| anyhow!("failed to push message: {e}"))?;
 
-        let instance_pre = self.state.instance_pre();
-        let mut store = Store::new(instance_pre.engine(), store_data);
-        let instance = instance_pre.instantiate_async(&mut store).await?;
         let messaging = Messaging::new(&mut store, &instance)?;
+        let tracked = qwasr::Invocation::start(instances.guest().name(), "messaging");
 
-        store
+        let invocation = store
             .run_concurrent(async |store| {
                 let guest = messaging.wasi_messaging_incoming_handler();
                 guest.call_handle(store, msg_res).await.map(|_| ()).context("issue sending message")
             })
-            .instrument(debug_span!("messaging-handle"))
-            .await?
+            .instrument(debug_span!("messaging-handle"));
+        let result = match &tracer {
+            Some(tracer) => tracer.scope(invocation).await,
+            None => invocation.await,
+        };
+        qwasr::record_fuel(&store, self.state.options(), instances.guest().name(), "messaging");
+        if let Some(profile) = profile {
+            profile.finish();
+        }
+        if let Some(tracer) = tracer {
+            tracer.finish();
+        }
+
+        let result = result.and_then(|result| result);
+        tracked.finish::<S>(&mut store, result.as_ref().err());
+        if let Err(e) = &result {
+            let options = self.state.options();
+            qwasr::save_core_dump(&mut store, e, options, instances.guest().name(), None).await;
+        }
+        result
     }
 
     // Get subscriptions for the topics configured in the wasm component.
     async fn subscriptions(&self) -> Result<Subscriptions> {
-        let instance_pre = self.state.instance_pre();
-        let store_data = self.state.store();
-        let mut store = Store::new(instance_pre.engine(), store_data);
+        let mut store = self.state.new_store();
 
         store
             .run_concurrent(async |store| {
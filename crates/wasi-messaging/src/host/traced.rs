@@ -0,0 +1,96 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::host::resource::{
+    Client, FutureResult, Message, MessageProxy, Metadata, Reply, RequestOptions, Subscriptions,
+};
+
+/// A client whose sends are recorded or replayed when the guest invocation is
+/// traced. The backend's client is `None` when replaying.
+#[derive(Debug)]
+pub struct TracedClient(pub Option<Arc<dyn Client>>);
+
+impl TracedClient {
+    // Call the backend's client.
+    fn call<T: Send + 'static>(
+        &self, call: impl FnOnce(&dyn Client) -> FutureResult<T>,
+    ) -> FutureResult<T> {
+        match &self.0 {
+            Some(client) => call(client.as_ref()),
+            None => Box::pin(async { Err(anyhow!("client is not connected when replaying")) }),
+        }
+    }
+}
+
+impl Client for TracedClient {
+    fn subscribe(&self) -> FutureResult<Subscriptions> {
+        self.call(Client::subscribe)
+    }
+
+    fn send(&self, topic: String, message: MessageProxy) -> FutureResult<()> {
+        qwasr::traced("messaging.send", (topic.clone(), message.payload()), || {
+            self.call(|client| client.send(topic, message))
+        })
+    }
+
+    fn request(
+        &self, topic: String, message: MessageProxy, options: Option<RequestOptions>,
+    ) -> FutureResult<MessageProxy> {
+        self.call(|client| client.request(topic, message, options))
+    }
+}
+
+/// A message delivered to a guest, as recorded in a trace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub metadata: Option<Metadata>,
+    pub description: Option<String>,
+    pub reply: Option<Reply>,
+}
+
+impl From<&dyn Message> for RecordedMessage {
+    fn from(message: &dyn Message) -> Self {
+        Self {
+            topic: message.topic(),
+            payload: message.payload(),
+            metadata: message.metadata(),
+            description: message.description(),
+            reply: message.reply(),
+        }
+    }
+}
+
+impl Message for RecordedMessage {
+    fn topic(&self) -> String {
+        self.topic.clone()
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        self.payload.clone()
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        self.metadata.clone()
+    }
+
+    fn description(&self) -> Option<String> {
+        self.description.clone()
+    }
+
+    fn length(&self) -> usize {
+        self.payload.len()
+    }
+
+    fn reply(&self) -> Option<Reply> {
+        self.reply.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -7,14 +7,20 @@ pub use crate::host::generated::wasi::messaging::types::{
     Error, Host, HostClient, HostClientWithStore, HostMessage, HostMessageWithStore, Topic,
 };
 use crate::host::resource::{ClientProxy, MessageProxy};
+use crate::host::traced::TracedClient;
 use crate::host::{Result, WasiMessaging, WasiMessagingCtxView};
 
 impl HostClientWithStore for WasiMessaging {
     async fn connect<T>(
         accessor: &Accessor<T, Self>, _name: String,
     ) -> Result<Resource<ClientProxy>> {
-        let client = accessor.with(|mut store| store.get().ctx.connect()).await?;
-        let proxy = ClientProxy(client);
+        let client = accessor
+            .with(|mut store| {
+                let ctx = store.get().ctx;
+                qwasr::traced_open("messaging.connect", (), || ctx.connect())
+            })
+            .await?;
+        let proxy = ClientProxy(Arc::new(TracedClient(client)));
         Ok(accessor.with(|mut store| store.get().table.push(proxy))?)
     }
 
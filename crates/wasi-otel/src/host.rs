@@ -21,6 +21,7 @@ mod generated {
     });
 }
 
+use std::any::Any;
 use std::fmt::Debug;
 
 use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
@@ -32,7 +33,7 @@ pub use self::default_impl::OtelDefault;
 use self::generated::wasi::otel::{metrics, resource, tracing, types};
 
 /// Host-side service for `wasi:otel`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WasiOtel;
 
 impl HasData for WasiOtel {
@@ -89,6 +90,24 @@ pub trait WasiOtelCtx: Debug + Send + Sync + 'static {
     fn export_metrics(&self, request: ExportMetricsServiceRequest) -> FutureResult<()>;
 }
 
+impl<B: WasiOtelCtx + Clone> qwasr::DynamicHost<B> for WasiOtel {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn WasiOtelCtx>)
+    }
+}
+
+impl WasiOtelView for qwasr::RuntimeCtx {
+    fn otel(&mut self) -> WasiOtelCtxView<'_> {
+        let (ctx, table, _) = self
+            .context::<Box<dyn WasiOtelCtx>>()
+            .expect("OpenTelemetry host context should be registered");
+        WasiOtelCtxView {
+            ctx: ctx.as_mut(),
+            table,
+        }
+    }
+}
+
 /// Implementation of the `WasiOtelView` trait for the store context.
 #[macro_export]
 macro_rules! qwasr_wasi_view {
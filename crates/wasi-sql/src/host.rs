@@ -5,6 +5,7 @@
 pub mod default_impl;
 mod readwrite_impl;
 mod resource;
+mod traced;
 mod types_impl;
 
 mod generated {
@@ -28,14 +29,16 @@ mod generated {
         trappable_error_type: {
             "wasi:sql/types.error" => Error,
         },
+        additional_derives: [serde::Serialize, serde::Deserialize],
     });
 }
 
+use std::any::Any;
 use std::fmt::Debug;
 use std::sync::Arc;
 
 pub use qwasr::FutureResult;
-use qwasr::{Host, Server, State};
+use qwasr::{Host, Policy, Server, State};
 use wasmtime::component::{HasData, Linker};
 use wasmtime_wasi::ResourceTable;
 
@@ -45,7 +48,7 @@ pub use crate::host::generated::wasi::sql::types::{DataType, Field, FormattedVal
 pub use crate::host::resource::*;
 
 /// Host-side service for `wasi:sql`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WasiSql;
 
 impl HasData for WasiSql {
@@ -80,6 +83,9 @@ pub struct WasiSqlCtxView<'a> {
 
     /// Mutable reference to table used to manage resources.
     pub table: &'a mut ResourceTable,
+
+    /// Capability policy limiting the resources the guest may access.
+    pub policy: &'a Policy,
 }
 
 /// A trait which provides internal WASI SQL context.
@@ -91,6 +97,38 @@ pub trait WasiSqlCtx: Debug + Send + Sync + 'static {
     fn open(&self, name: String) -> FutureResult<Arc<dyn Connection>>;
 }
 
+impl<B: WasiSqlCtx + Clone> qwasr::DynamicHost<B> for WasiSql {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn WasiSqlCtx>)
+    }
+}
+
+impl WasiSqlView for qwasr::RuntimeCtx {
+    fn sql(&mut self) -> WasiSqlCtxView<'_> {
+        let (ctx, table, policy) =
+            self.context::<Box<dyn WasiSqlCtx>>().expect("SQL host context should be registered");
+        WasiSqlCtxView {
+            ctx: ctx.as_mut(),
+            table,
+            policy,
+        }
+    }
+}
+
+impl qwasr::NamedHost for WasiSql {
+    type Ctx = dyn WasiSqlCtx;
+}
+
+// Forward to the backend bound to the name opened by the guest.
+impl WasiSqlCtx for qwasr::NamedBackends<dyn WasiSqlCtx> {
+    fn open(&self, name: String) -> FutureResult<Arc<dyn Connection>> {
+        match self.get(&name) {
+            Ok(backend) => backend.open(name),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+}
+
 /// Implementation of the `WasiSqlView` trait for the store context.
 #[macro_export]
 macro_rules! qwasr_wasi_view {
@@ -100,6 +138,7 @@ macro_rules! qwasr_wasi_view {
                 qwasr_wasi_sql::WasiSqlCtxView {
                     ctx: &mut self.$field_name,
                     table: &mut self.table,
+                    policy: &self.policy,
                 }
             }
         }
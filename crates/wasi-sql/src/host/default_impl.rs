@@ -36,6 +36,10 @@ impl qwasr::FromEnv for ConnectOptions {
     fn from_env() -> Result<Self> {
         Self::from_env().finalize().context("issue loading connection options")
     }
+
+    fn env_vars() -> Vec<String> {
+        qwasr::requirement_vars(&Self::requirements())
+    }
 }
 
 /// Default implementation for `wasi:sql`.
@@ -60,6 +64,12 @@ impl Backend for SqlDefault {
             database_path: options.database,
         })
     }
+
+    async fn health_check(&self) -> Result<()> {
+        let conn = SqliteConnection::open(&self.database_path)
+            .context("failed to open SQLite database")?;
+        conn.query_row("SELECT 1", [], |_| Ok(())).context("failed to query SQLite database")
+    }
 }
 
 impl WasiSqlCtx for SqlDefault {
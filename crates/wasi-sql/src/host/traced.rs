@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use crate::host::resource::{Connection, FutureResult};
+use crate::{DataType, Row};
+
+/// A connection whose queries are recorded or replayed when the guest
+/// invocation is traced. The backend's connection is `None` when replaying.
+#[derive(Debug)]
+pub struct TracedConnection {
+    pub name: String,
+    pub connection: Option<Arc<dyn Connection>>,
+}
+
+impl TracedConnection {
+    // Call the backend's connection.
+    fn call<T: Send + 'static>(
+        &self, call: impl FnOnce(&dyn Connection) -> FutureResult<T>,
+    ) -> FutureResult<T> {
+        match &self.connection {
+            Some(connection) => call(connection.as_ref()),
+            None => Box::pin(async { Err(anyhow!("connection is not open when replaying")) }),
+        }
+    }
+}
+
+impl Connection for TracedConnection {
+    fn query(&self, query: String, params: Vec<DataType>) -> FutureResult<Vec<Row>> {
+        qwasr::traced("sql.query", (&self.name, query.clone(), params.clone()), || {
+            self.call(|connection| connection.query(query, params))
+        })
+    }
+
+    fn exec(&self, query: String, params: Vec<DataType>) -> FutureResult<u32> {
+        qwasr::traced("sql.exec", (&self.name, query.clone(), params.clone()), || {
+            self.call(|connection| connection.exec(query, params))
+        })
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use anyhow::Result;
 use wasmtime::component::{Access, Accessor, Resource};
 
@@ -6,17 +8,30 @@ use crate::host::generated::wasi::sql::types::{
     HostErrorWithStore, HostStatement, HostStatementWithStore, Statement,
 };
 use crate::host::resource::ConnectionProxy;
+use crate::host::traced::TracedConnection;
 use crate::host::{WasiSql, WasiSqlCtxView};
 
 impl HostConnectionWithStore for WasiSql {
     async fn open<T>(
         accessor: &Accessor<T, Self>, name: String,
     ) -> Result<Result<Resource<Connection>, Resource<Error>>> {
-        let open_conn = accessor.with(|mut store| store.get().ctx.open(name)).await;
+        let allowed =
+            accessor.with(|mut store| store.get().policy.check("sql", "connections", &name));
+        let open_conn = match allowed {
+            Ok(()) => {
+                accessor
+                    .with(|mut store| {
+                        let ctx = store.get().ctx;
+                        qwasr::traced_open("sql.open", name.clone(), || ctx.open(name.clone()))
+                    })
+                    .await
+            }
+            Err(e) => Err(e),
+        };
 
         let result = match open_conn {
-            Ok(conn) => {
-                let proxy = ConnectionProxy(conn);
+            Ok(connection) => {
+                let proxy = ConnectionProxy(Arc::new(TracedConnection { name, connection }));
                 Ok(accessor.with(|mut store| store.get().table.push(proxy))?)
             }
             Err(err) => Err(accessor.with(|mut store| store.get().table.push(err))?),
@@ -26,11 +26,12 @@ mod generated {
     });
 }
 
+use std::any::Any;
 use std::fmt::Debug;
 use std::sync::Arc;
 
 pub use qwasr::FutureResult;
-use qwasr::{Host, Server, State};
+use qwasr::{Host, Policy, Server, State};
 use wasmtime::component::{HasData, Linker};
 use wasmtime_wasi::ResourceTable;
 
@@ -39,7 +40,7 @@ pub use crate::host::default_impl::VaultDefault;
 pub use crate::host::resource::*;
 
 /// Host-side service for `wasi:vault`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WasiVault;
 
 impl HasData for WasiVault {
@@ -73,6 +74,9 @@ pub struct WasiVaultCtxView<'a> {
 
     /// Mutable reference to table used to manage resources.
     pub table: &'a mut ResourceTable,
+
+    /// Capability policy limiting the resources the guest may access.
+    pub policy: &'a Policy,
 }
 
 /// A trait which provides internal WASI Vault context.
@@ -84,6 +88,39 @@ pub trait WasiVaultCtx: Debug + Send + Sync + 'static {
     fn open_locker(&self, identifier: String) -> FutureResult<Arc<dyn Locker>>;
 }
 
+impl<B: WasiVaultCtx + Clone> qwasr::DynamicHost<B> for WasiVault {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn WasiVaultCtx>)
+    }
+}
+
+impl WasiVaultView for qwasr::RuntimeCtx {
+    fn vault(&mut self) -> WasiVaultCtxView<'_> {
+        let (ctx, table, policy) = self
+            .context::<Box<dyn WasiVaultCtx>>()
+            .expect("vault host context should be registered");
+        WasiVaultCtxView {
+            ctx: ctx.as_mut(),
+            table,
+            policy,
+        }
+    }
+}
+
+impl qwasr::NamedHost for WasiVault {
+    type Ctx = dyn WasiVaultCtx;
+}
+
+// Forward to the backend bound to the name opened by the guest.
+impl WasiVaultCtx for qwasr::NamedBackends<dyn WasiVaultCtx> {
+    fn open_locker(&self, identifier: String) -> FutureResult<Arc<dyn Locker>> {
+        match self.get(&identifier) {
+            Ok(backend) => backend.open_locker(identifier),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+}
+
 /// Implementation of the `WasiVaultView` trait for the store context.
 #[macro_export]
 macro_rules! qwasr_wasi_view {
@@ -93,6 +130,7 @@ macro_rules! qwasr_wasi_view {
                 qwasr_wasi_vault::WasiVaultCtxView {
                     ctx: &mut self.$field_name,
                     table: &mut self.table,
+                    policy: &self.policy,
                 }
             }
         }
@@ -12,6 +12,9 @@ impl HostWithStore for WasiVault {
     async fn open<T>(
         accessor: &Accessor<T, Self>, locker_id: String,
     ) -> Result<Resource<LockerProxy>> {
+        if !accessor.with(|mut store| store.get().policy.allows("vault", "lockers", &locker_id)) {
+            return Err(Error::AccessDenied);
+        }
         let locker = accessor.with(|mut store| store.get().ctx.open_locker(locker_id)).await?;
         let proxy = LockerProxy(locker);
         Ok(accessor.with(|mut store| store.get().table.push(proxy))?)
@@ -27,11 +27,12 @@ mod generated {
     });
 }
 
+use std::any::Any;
 use std::fmt::Debug;
 use std::sync::Arc;
 
 use anyhow::Result;
-use qwasr::{Host, Server, State};
+use qwasr::{Host, Server, Shutdown, State};
 use server::run_server;
 use store_impl::FutureResult;
 use wasmtime::component::{HasData, Linker};
@@ -41,7 +42,7 @@ pub use self::default_impl::WebSocketsDefault;
 use self::generated::wasi::websockets::{store, types as generated_types};
 
 /// Host-side service for `wasi:websockets`.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct WasiWebSockets;
 
 impl HasData for WasiWebSockets {
@@ -55,6 +56,10 @@ where
     fn add_to_linker(linker: &mut Linker<T>) -> Result<()> {
         store::add_to_linker::<_, Self>(linker, T::websockets)
     }
+
+    fn env_vars() -> Vec<String> {
+        vec!["WEBSOCKETS_ADDR".into()]
+    }
 }
 
 impl<S> Server<S> for WasiWebSockets
@@ -64,8 +69,8 @@ where
 {
     /// Provide http proxy service the specified wasm component.
     /// ``state`` will be used at a later time to provide resource access to guest handlers
-    async fn run(&self, state: &S) -> Result<()> {
-        run_server(state).await
+    async fn run(&self, state: &S, shutdown: Shutdown) -> Result<()> {
+        run_server(state, shutdown).await
     }
 }
 
@@ -102,6 +107,21 @@ impl generated_types::Host for WasiWebSocketsCtxView<'_> {
     }
 }
 
+impl<B: WebSocketsCtx + Clone> qwasr::DynamicHost<B> for WasiWebSockets {
+    fn context(backend: &B) -> Box<dyn Any + Send> {
+        Box::new(Box::new(backend.clone()) as Box<dyn WebSocketsCtx>)
+    }
+}
+
+impl WebSocketsView for qwasr::RuntimeCtx {
+    fn websockets(&mut self) -> WasiWebSocketsCtxView<'_> {
+        let (ctx, table, _) = self
+            .context::<Box<dyn WebSocketsCtx>>()
+            .expect("websockets host context should be registered");
+        WasiWebSocketsCtxView { ctx: &**ctx, table }
+    }
+}
+
 /// Implementation of the `WebSocketsView` trait for the store context.
 #[macro_export]
 macro_rules! qwasr_wasi_view {
@@ -18,7 +18,7 @@ use hyper::service::service_fn;
 use hyper::upgrade::Upgraded;
 use hyper::{Method, Request, Response, StatusCode, Version};
 use hyper_util::rt::TokioIo;
-use qwasr::State;
+use qwasr::{Shutdown, State};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{Mutex, OnceCell};
 use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
@@ -202,7 +202,7 @@ async fn handle_request(
 }
 
 #[allow(clippy::missing_errors_doc)]
-pub async fn run_server<S>(_: &S) -> Result<()>
+pub async fn run_server<S>(_: &S, shutdown: Shutdown) -> Result<()>
 where
     S: State,
     S::StoreCtx: WebSocketsView,
@@ -214,8 +214,12 @@ where
     let listener = TcpListener::bind(&addr).await?;
     tracing::info!("websocket server listening on: {}", listener.local_addr()?);
 
+    // accept connections until shutdown
     loop {
-        let (stream, peer_addr) = listener.accept().await?;
+        let (stream, peer_addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            () = shutdown.signalled() => break,
+        };
         let peer = stream.peer_addr().expect("connected streams should have a peer address");
         tracing::info!("Peer address: {}", peer);
         let state_ref = Arc::<StdMutex<HashMap<SocketAddr, PeerInfo>>>::clone(&state);
@@ -238,4 +242,7 @@ where
             }
         });
     }
+
+    tracing::info!("websocket server stopped accepting connections");
+    Ok(())
 }
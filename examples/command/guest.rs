@@ -0,0 +1,40 @@
+//! # Command Wasm Guest
+//!
+//! This module demonstrates a one-shot job exporting `wasi:cli/run`. It shows
+//! how to:
+//! - Read the arguments passed with `qwasr exec`
+//! - Use the same key-value host as server guests
+//! - Fail the job, exiting with a non-zero exit code
+
+#![cfg(target_arch = "wasm32")]
+
+use qwasr_wasi_keyvalue::store;
+use wasip3::cli::environment;
+use wasip3::exports::cli::run::Guest;
+
+struct Command;
+wasip3::cli::command::export!(Command);
+
+impl Guest for Command {
+    /// Stores each argument in the `jobs` bucket, failing when no arguments
+    /// are given.
+    async fn run() -> Result<(), ()> {
+        // the first argument is the program name
+        let args = environment::get_arguments().into_iter().skip(1).collect::<Vec<_>>();
+        if args.is_empty() {
+            eprintln!("usage: command -- <value>...");
+            return Err(());
+        }
+
+        let bucket = store::open("jobs".to_string()).await.map_err(|e| eprintln!("{e:?}"))?;
+        for (i, arg) in args.iter().enumerate() {
+            bucket
+                .set(format!("arg-{i}"), arg.clone().into_bytes())
+                .await
+                .map_err(|e| eprintln!("{e:?}"))?;
+        }
+
+        println!("stored {} arguments", args.len());
+        Ok(())
+    }
+}
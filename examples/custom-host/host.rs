@@ -0,0 +1,92 @@
+//! An in-house host, wired into the runtime through `qwasr::HostContext`
+//! rather than a view macro.
+
+use std::env;
+use std::fmt::Debug;
+
+use anyhow::Result;
+use qwasr::wasmtime::component::Linker;
+use qwasr::{Backend, FromEnv, Host, HostContext, Server, State};
+
+/// Host-side service for `example:greeter/greet`.
+#[derive(Debug)]
+pub struct Greeter;
+
+impl<T> Host<T> for Greeter
+where
+    T: GreeterView + 'static,
+{
+    fn add_to_linker(linker: &mut Linker<T>) -> Result<()> {
+        linker
+            .instance("example:greeter/greet@0.1.0")?
+            .func_wrap("greet", |mut store, (name,): (String,)| {
+                Ok((store.data_mut().greeter().greet(&name),))
+            })
+    }
+}
+
+impl<S: State> Server<S> for Greeter {}
+
+/// Provides the host's context from the store context.
+pub trait GreeterView: Send {
+    /// Return the [`GreeterCtx`] from mutable reference to self.
+    fn greeter(&mut self) -> &dyn GreeterCtx;
+}
+
+/// Implemented by backends providing greetings.
+pub trait GreeterCtx: Debug + Send + Sync + 'static {
+    /// Greet `name`.
+    fn greet(&self, name: &str) -> String;
+}
+
+// Any store context linking `Greeter` provides its view, so no view macro is
+// needed.
+impl<T> GreeterView for T
+where
+    T: HostContext<Greeter> + Send,
+    T::Context: GreeterCtx,
+{
+    fn greeter(&mut self) -> &dyn GreeterCtx {
+        self.host_context().0
+    }
+}
+
+/// Default greeter, configured with `GREETING`.
+#[derive(Debug, Clone)]
+pub struct GreeterDefault {
+    greeting: String,
+}
+
+impl GreeterCtx for GreeterDefault {
+    fn greet(&self, name: &str) -> String {
+        format!("{}, {name}!", self.greeting)
+    }
+}
+
+/// Options used to create the default greeter.
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    greeting: String,
+}
+
+impl FromEnv for ConnectOptions {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            greeting: env::var("GREETING").unwrap_or_else(|_| "Hello".to_string()),
+        })
+    }
+
+    fn env_vars() -> Vec<String> {
+        vec!["GREETING".into()]
+    }
+}
+
+impl Backend for GreeterDefault {
+    type ConnectOptions = ConnectOptions;
+
+    async fn connect_with(options: Self::ConnectOptions) -> Result<Self> {
+        Ok(Self {
+            greeting: options.greeting,
+        })
+    }
+}
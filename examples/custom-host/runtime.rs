@@ -0,0 +1,21 @@
+//! In-house host example runtime.
+
+cfg_if::cfg_if! {
+    if #[cfg(not(target_arch = "wasm32"))] {
+        mod host;
+
+        use qwasr_wasi_http::{WasiHttp, HttpDefault};
+        use qwasr_wasi_otel::{WasiOtel, OtelDefault};
+
+        qwasr::runtime!({
+            main: true,
+            hosts: {
+                WasiHttp: HttpDefault,
+                WasiOtel: OtelDefault,
+                host::Greeter: host::GreeterDefault,
+            }
+        });
+    } else {
+        fn main() {}
+    }
+}
@@ -0,0 +1,22 @@
+//! Runtime builder example runtime.
+
+cfg_if::cfg_if! {
+    if #[cfg(not(target_arch = "wasm32"))] {
+        use qwasr::RuntimeBuilder;
+        use qwasr_wasi_blobstore::{WasiBlobstore, BlobstoreDefault};
+        use qwasr_wasi_http::{WasiHttp, HttpDefault};
+        use qwasr_wasi_keyvalue::{WasiKeyValue, KeyValueDefault};
+        use qwasr_wasi_otel::{WasiOtel, OtelDefault};
+
+        fn main() -> anyhow::Result<std::process::ExitCode> {
+            RuntimeBuilder::new()
+                .host::<WasiHttp, HttpDefault>("http", "default")
+                .host::<WasiOtel, OtelDefault>("otel", "default")
+                .host::<WasiKeyValue, KeyValueDefault>("keyvalue", "memory")
+                .host::<WasiBlobstore, BlobstoreDefault>("blobstore", "memory")
+                .run_cli()
+        }
+    } else {
+        fn main() {}
+    }
+}
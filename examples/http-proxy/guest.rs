@@ -3,7 +3,7 @@
 //! This module demonstrates an HTTP proxy pattern with caching using WASI HTTP.
 //! It shows how to:
 //! - Make outbound HTTP requests from a WebAssembly guest
-//! - Implement HTTP caching with ETags and Cache-Control headers
+//! - Implement HTTP caching with ETag and Cache-Control headers
 //! - Use client certificates for mTLS authentication
 //!
 //! ## Caching Strategy
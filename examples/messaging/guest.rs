@@ -39,7 +39,7 @@ use wasip3::http::types::{ErrorCode, Request, Response};
 // HTTP Interface
 // ----------------------------------------------------------------------------
 
-pub struct Http;
+struct Http;
 wasip3::http::proxy::export!(Http);
 
 impl Guest for Http {
@@ -90,7 +90,7 @@ async fn request_reply_handler(body: Bytes) -> Json<Value> {
 // Messaging Interface
 // ----------------------------------------------------------------------------
 
-pub struct Messaging;
+struct Messaging;
 qwasr_wasi_messaging::export!(Messaging with_types_in qwasr_wasi_messaging);
 
 impl qwasr_wasi_messaging::incoming_handler::Guest for Messaging {
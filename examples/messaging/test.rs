@@ -0,0 +1,211 @@
+//! Messaging example test.
+//!
+//! Runs the messaging guest in-process with `qwasr::testing`, linked with a
+//! fake messaging backend that records the messages the guest sends.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::any::Any;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Result, bail};
+use futures::{FutureExt, stream};
+use qwasr::RuntimeOptions;
+use qwasr::testing::TestRuntime;
+use qwasr_wasi_http::{HttpDefault, WasiHttp};
+use qwasr_wasi_messaging::{
+    Client, FutureResult, Message, MessageProxy, Metadata, Reply, RequestOptions, Subscriptions,
+    WasiMessaging, WasiMessagingCtx,
+};
+use qwasr_wasi_otel::{OtelDefault, WasiOtel};
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn messaging() {
+    let backend = FakeMessaging::default();
+    let options = RuntimeOptions {
+        warm_instances: 1,
+        ..<RuntimeOptions as qwasr::FromEnv>::from_env().expect("should load options")
+    };
+    let runtime = TestRuntime::builder()
+        .host::<WasiHttp, _>(HttpDefault)
+        .host::<WasiMessaging, _>(backend.clone())
+        .host::<WasiOtel, _>(OtelDefault)
+        .options(options)
+        .build(guest())
+        .expect("should build runtime");
+
+    // publishing over HTTP sends the request body to topic `a`
+    let request = http::Request::post("/pub-sub")
+        .header("content-type", "application/json")
+        .body(r#"{"text":"hello"}"#)
+        .expect("should build request");
+    let response = qwasr_wasi_http::handle(&runtime, request).await.expect("should handle");
+    assert_eq!(response.status(), 200);
+    let body: Value = serde_json::from_slice(response.body()).expect("should be json");
+    assert_eq!(body, json!({"message": "message published"}));
+    assert_eq!(backend.sent(), [("a".to_string(), br#"{"text":"hello"}"#.to_vec())]);
+
+    // messages on topic `c` are replied to on the message's reply topic
+    let message = TestMessage {
+        topic: "c".to_string(),
+        payload: b"world".to_vec(),
+        reply: Some(Reply {
+            client_name: "default".to_string(),
+            topic: "replies".to_string(),
+        }),
+        ..TestMessage::default()
+    };
+    qwasr_wasi_messaging::deliver(&runtime, MessageProxy(Arc::new(message)))
+        .await
+        .expect("should deliver");
+    assert_eq!(backend.sent()[1], ("replies".to_string(), b"Hello from topic c: world".to_vec()));
+}
+
+// Build the guest, returning the path to its wasm.
+fn guest() -> PathBuf {
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--example", "messaging-wasm", "--target", "wasm32-wasip2"])
+        .status()
+        .expect("should run cargo");
+    assert!(status.success(), "should build guest");
+
+    let target = std::env::var_os("CARGO_TARGET_DIR")
+        .map_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../target"), PathBuf::from);
+    target.join("wasm32-wasip2/debug/examples/messaging_wasm.wasm")
+}
+
+// Messages sent, with the topic each was sent to.
+type Sent = Vec<(String, Vec<u8>)>;
+
+/// Messaging backend recording each message sent, with its topic.
+#[derive(Debug, Clone, Default)]
+struct FakeMessaging {
+    sent: Arc<Mutex<Sent>>,
+}
+
+impl FakeMessaging {
+    fn sent(&self) -> Sent {
+        self.sent.lock().expect("should lock").clone()
+    }
+}
+
+impl WasiMessagingCtx for FakeMessaging {
+    fn connect(&self) -> FutureResult<Arc<dyn Client>> {
+        let client = self.clone();
+        async move { Ok(Arc::new(client) as Arc<dyn Client>) }.boxed()
+    }
+
+    fn new_message(&self, data: Vec<u8>) -> Result<Arc<dyn Message>> {
+        Ok(Arc::new(TestMessage {
+            payload: data,
+            ..TestMessage::default()
+        }))
+    }
+
+    fn set_content_type(
+        &self, message: Arc<dyn Message>, content_type: String,
+    ) -> Result<Arc<dyn Message>> {
+        self.add_metadata(message, "content-type".to_string(), content_type)
+    }
+
+    fn set_payload(&self, message: Arc<dyn Message>, data: Vec<u8>) -> Result<Arc<dyn Message>> {
+        update(&message, |message| message.payload = data)
+    }
+
+    fn add_metadata(
+        &self, message: Arc<dyn Message>, key: String, value: String,
+    ) -> Result<Arc<dyn Message>> {
+        update(&message, |message| {
+            message.metadata.get_or_insert_default().insert(key, value);
+        })
+    }
+
+    fn set_metadata(
+        &self, message: Arc<dyn Message>, metadata: Metadata,
+    ) -> Result<Arc<dyn Message>> {
+        update(&message, |message| message.metadata = Some(metadata))
+    }
+
+    fn remove_metadata(&self, message: Arc<dyn Message>, key: String) -> Result<Arc<dyn Message>> {
+        update(&message, |message| {
+            if let Some(metadata) = &mut message.metadata {
+                metadata.remove(&key);
+            }
+        })
+    }
+}
+
+impl Client for FakeMessaging {
+    fn subscribe(&self) -> FutureResult<Subscriptions> {
+        async { Ok(Box::pin(stream::empty()) as Subscriptions) }.boxed()
+    }
+
+    fn send(&self, topic: String, message: MessageProxy) -> FutureResult<()> {
+        self.sent.lock().expect("should lock").push((topic, message.payload()));
+        async { Ok(()) }.boxed()
+    }
+
+    fn request(
+        &self, topic: String, message: MessageProxy, _options: Option<RequestOptions>,
+    ) -> FutureResult<MessageProxy> {
+        self.sent.lock().expect("should lock").push((topic, message.payload()));
+        let reply = TestMessage {
+            payload: b"ACK".to_vec(),
+            ..TestMessage::default()
+        };
+        async move { Ok(MessageProxy(Arc::new(reply))) }.boxed()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct TestMessage {
+    topic: String,
+    payload: Vec<u8>,
+    metadata: Option<Metadata>,
+    reply: Option<Reply>,
+}
+
+// Copy `message`, applying `f` to the copy.
+fn update(
+    message: &Arc<dyn Message>, f: impl FnOnce(&mut TestMessage),
+) -> Result<Arc<dyn Message>> {
+    let Some(message) = message.as_any().downcast_ref::<TestMessage>() else {
+        bail!("invalid message type");
+    };
+    let mut updated = message.clone();
+    f(&mut updated);
+    Ok(Arc::new(updated))
+}
+
+impl Message for TestMessage {
+    fn topic(&self) -> String {
+        self.topic.clone()
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        self.payload.clone()
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        self.metadata.clone()
+    }
+
+    fn description(&self) -> Option<String> {
+        None
+    }
+
+    fn length(&self) -> usize {
+        self.payload.len()
+    }
+
+    fn reply(&self) -> Option<Reply> {
+        self.reply.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}